@@ -0,0 +1,484 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_alt_ui_navigation_lite::prelude::*;
+
+use crate::{
+    loading::GameAssets,
+    localization::Localization,
+    settings::{AchievementsSetting, LifetimeStatsSetting},
+    stats::RunStats,
+    ui::{buttons, Palette},
+    GameState, Player, TrickEvent, LAVA,
+};
+
+/// How long an "Achievement Unlocked" toast stays on screen.
+const TOAST_SECONDS: f32 = 3.;
+
+pub struct AchievementsPlugin;
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AchievementUnlocked>()
+            .init_resource::<ToastQueue>()
+            .add_systems(Update, count_tricks.run_if(in_state(GameState::Playing)))
+            .add_systems(OnEnter(GameState::Leaderboard), race_finished)
+            .add_systems(OnEnter(GameState::GameOver), race_over)
+            .add_systems(
+                Update,
+                (queue_toasts, show_toasts)
+                    .chain()
+                    .run_if(resource_exists::<GameAssets>),
+            )
+            .add_systems(OnEnter(GameState::Achievements), spawn)
+            .add_systems(
+                Update,
+                (button_actions, buttons.after(NavRequestSystem))
+                    .run_if(in_state(GameState::Achievements)),
+            )
+            .add_systems(OnExit(GameState::Achievements), cleanup);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    FirstFinish,
+    NoBonks,
+    FakieFlip,
+    QuadFrontFlip,
+    QuadBackFlip,
+    MegaFlip,
+    HundredFlips,
+    Veteran,
+    LavaDeath,
+}
+impl Achievement {
+    pub const ALL: [Achievement; 9] = [
+        Achievement::FirstFinish,
+        Achievement::NoBonks,
+        Achievement::FakieFlip,
+        Achievement::QuadFrontFlip,
+        Achievement::QuadBackFlip,
+        Achievement::MegaFlip,
+        Achievement::HundredFlips,
+        Achievement::Veteran,
+        Achievement::LavaDeath,
+    ];
+
+    /// The key used in the save file. Don't change these.
+    fn id(&self) -> &'static str {
+        match self {
+            Self::FirstFinish => "first_finish",
+            Self::NoBonks => "no_bonks",
+            Self::FakieFlip => "fakie_flip",
+            Self::QuadFrontFlip => "quad_front_flip",
+            Self::QuadBackFlip => "quad_back_flip",
+            Self::MegaFlip => "mega_flip",
+            Self::HundredFlips => "hundred_flips",
+            Self::Veteran => "veteran",
+            Self::LavaDeath => "lava_death",
+        }
+    }
+
+    fn name(&self, localization: &Localization) -> String {
+        localization
+            .get(&format!("achievement.{}.name", self.id()))
+            .to_string()
+    }
+
+    fn description(&self, localization: &Localization) -> String {
+        localization
+            .get(&format!("achievement.{}.description", self.id()))
+            .to_string()
+    }
+
+    fn is_unlocked(&self, unlocked: &AchievementsSetting) -> bool {
+        unlocked.iter().any(|id| id == self.id())
+    }
+}
+
+#[derive(Event)]
+pub struct AchievementUnlocked(pub Achievement);
+
+/// Achievements waiting to be shown in a toast, oldest first.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct ToastQueue(VecDeque<Achievement>);
+
+#[derive(Component, Deref, DerefMut)]
+struct Toast(Timer);
+
+#[derive(Component)]
+struct AchievementsMarker;
+
+#[derive(Component)]
+enum AchievementsButton {
+    Back,
+}
+
+fn unlock(
+    achievement: Achievement,
+    unlocked: &mut AchievementsSetting,
+    events: &mut EventWriter<AchievementUnlocked>,
+) {
+    if achievement.is_unlocked(unlocked) {
+        return;
+    }
+
+    info!("unlocked achievement {:?}", achievement);
+
+    unlocked.push(achievement.id().to_string());
+    events.send(AchievementUnlocked(achievement));
+}
+
+fn count_tricks(
+    mut tricks: EventReader<TrickEvent>,
+    mut lifetime: ResMut<LifetimeStatsSetting>,
+    mut unlocked: ResMut<AchievementsSetting>,
+    mut events: EventWriter<AchievementUnlocked>,
+) {
+    for TrickEvent(trick) in tricks.read() {
+        lifetime.total_flips += trick.front_flips + trick.back_flips;
+
+        if trick.fakie {
+            unlock(Achievement::FakieFlip, &mut unlocked, &mut events);
+        }
+        if trick.front_flips >= 4 {
+            unlock(Achievement::QuadFrontFlip, &mut unlocked, &mut events);
+        }
+        if trick.back_flips >= 4 {
+            unlock(Achievement::QuadBackFlip, &mut unlocked, &mut events);
+        }
+        if trick.front_flips >= 5 || trick.back_flips >= 5 {
+            lifetime.mega_flips += 1;
+            unlock(Achievement::MegaFlip, &mut unlocked, &mut events);
+        }
+        if lifetime.total_flips >= 100 {
+            unlock(Achievement::HundredFlips, &mut unlocked, &mut events);
+        }
+    }
+}
+
+fn race_finished(
+    run_stats: Res<RunStats>,
+    mut lifetime: ResMut<LifetimeStatsSetting>,
+    mut unlocked: ResMut<AchievementsSetting>,
+    mut events: EventWriter<AchievementUnlocked>,
+) {
+    lifetime.races_finished += 1;
+    lifetime.bonks += run_stats.bonks;
+
+    unlock(Achievement::FirstFinish, &mut unlocked, &mut events);
+    if run_stats.bonks == 0 {
+        unlock(Achievement::NoBonks, &mut unlocked, &mut events);
+    }
+    if lifetime.races_finished >= 25 {
+        unlock(Achievement::Veteran, &mut unlocked, &mut events);
+    }
+}
+
+fn race_over(
+    run_stats: Res<RunStats>,
+    query: Query<&Transform, With<Player>>,
+    mut lifetime: ResMut<LifetimeStatsSetting>,
+    mut unlocked: ResMut<AchievementsSetting>,
+    mut events: EventWriter<AchievementUnlocked>,
+) {
+    lifetime.bonks += run_stats.bonks;
+
+    // The race is also over when the player resets, but that's not nearly as fun.
+    if query.iter().any(|transform| transform.translation.y < LAVA) {
+        lifetime.lava_deaths += 1;
+        unlock(Achievement::LavaDeath, &mut unlocked, &mut events);
+    }
+}
+
+fn queue_toasts(mut events: EventReader<AchievementUnlocked>, mut queue: ResMut<ToastQueue>) {
+    for AchievementUnlocked(achievement) in events.read() {
+        queue.push_back(*achievement);
+    }
+}
+
+/// Shows the queued achievements one at a time in the corner of the screen.
+///
+/// Toasts keep real time, so they don't get stuck on screen while the game is paused.
+fn show_toasts(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    mut queue: ResMut<ToastQueue>,
+    mut toast_query: Query<(Entity, &mut Toast)>,
+    palette: Res<Palette>,
+) {
+    let mut showing = false;
+
+    for (entity, mut toast) in &mut toast_query {
+        toast.tick(time.delta());
+        if toast.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            showing = true;
+        }
+    }
+
+    if showing {
+        return;
+    }
+
+    let Some(achievement) = queue.pop_front() else {
+        return;
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(5.),
+                    top: Val::Px(5.),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(10.)),
+                    ..default()
+                },
+                background_color: palette.container_background.into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            Toast(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once)),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                localization.get("achievements.unlocked"),
+                TextStyle {
+                    font: localization.font(&assets),
+                    font_size: 24.0,
+                    color: palette.faster_text,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                achievement.name(&localization),
+                TextStyle {
+                    font: localization.font(&assets),
+                    font_size: 30.0,
+                    color: palette.title_text,
+                },
+            ));
+        });
+}
+
+fn spawn(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    unlocked: Res<AchievementsSetting>,
+    lifetime: Res<LifetimeStatsSetting>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    let font = localization.font(&assets);
+
+    let title_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 60.0,
+        color: palette.title_text,
+    };
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(45.0),
+        margin: UiRect::all(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.button_text,
+    };
+    let name_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.title_text,
+    };
+    let description_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 24.0,
+        color: palette.title_text,
+    };
+
+    let num_unlocked = Achievement::ALL
+        .iter()
+        .filter(|achievement| achievement.is_unlocked(&unlocked))
+        .count();
+
+    let root = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                ..default()
+            },
+            AchievementsMarker,
+        ))
+        .id();
+
+    let container = commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            background_color: palette.container_background.into(),
+            ..default()
+        })
+        .id();
+
+    let title = commands
+        .spawn(
+            TextBundle::from_section(localization.get("achievements.title"), title_text_style)
+                .with_style(Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+        )
+        .id();
+
+    let progress = commands
+        .spawn(
+            TextBundle::from_section(
+                localization.format(
+                    "achievements.progress",
+                    &[&num_unlocked, &Achievement::ALL.len()],
+                ),
+                description_text_style.clone(),
+            )
+            .with_style(Style {
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .id();
+
+    let list = commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: vec![GridTrack::auto(), GridTrack::auto()],
+                column_gap: Val::Px(20.),
+                align_items: AlignItems::Center,
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for achievement in Achievement::ALL {
+                let color = if achievement.is_unlocked(&unlocked) {
+                    palette.title_text
+                } else {
+                    palette.locked_text
+                };
+
+                parent.spawn(TextBundle::from_section(
+                    achievement.name(&localization),
+                    TextStyle {
+                        color,
+                        ..name_text_style.clone()
+                    },
+                ));
+                parent.spawn(TextBundle::from_section(
+                    achievement.description(&localization),
+                    TextStyle {
+                        color,
+                        ..description_text_style.clone()
+                    },
+                ));
+            }
+        })
+        .id();
+
+    let stats = commands
+        .spawn(
+            TextBundle::from_section(
+                localization.format(
+                    "achievements.lifetime",
+                    &[
+                        &lifetime.races_finished,
+                        &lifetime.total_flips,
+                        &lifetime.mega_flips,
+                        &lifetime.bonks,
+                        &lifetime.lava_deaths,
+                    ],
+                ),
+                description_text_style,
+            )
+            .with_style(Style {
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .id();
+
+    let back = commands
+        .spawn((
+            ButtonBundle {
+                style: button_style,
+                background_color: palette.normal_button.into(),
+                ..default()
+            },
+            Focusable::default(),
+            AchievementsButton::Back,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                localization.get("common.back"),
+                button_text_style,
+            ));
+        })
+        .id();
+
+    commands.entity(root).push_children(&[container]);
+
+    commands
+        .entity(container)
+        .push_children(&[title, progress, list, stats, back]);
+}
+
+fn button_actions(
+    buttons: Query<&AchievementsButton>,
+    mut events: EventReader<NavEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for button in events.nav_iter().activated_in_query(&buttons) {
+        match button {
+            AchievementsButton::Back => {
+                next_state.set(GameState::MainMenu);
+            }
+        }
+    }
+}
+
+fn cleanup(mut commands: Commands, query: Query<Entity, With<AchievementsMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
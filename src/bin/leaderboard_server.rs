@@ -0,0 +1,244 @@
+//! A stand-in leaderboard server for the http leaderboard backend.
+//!
+//! ```text
+//! cargo run --bin leaderboard_server -- 127.0.0.1:8080 scores.tsv
+//! COMBINE_RACERS_LEADERBOARD=http://127.0.0.1:8080 cargo run
+//! ```
+//!
+//! Scores are kept in memory and appended to the optional scores file, which is read
+//! back in on startup. Each line of that file is `<track>\t<score>\t<player>\t<replay>`.
+//!
+//! If `COMBINE_RACERS_VERIFIER` is set to the path of the `verify_replay` binary, each
+//! score's replay is run through it and rejected if it doesn't check out.
+//!
+//! A score that's already on the leaderboard is answered as if it had been added
+//! again, since a client that gave up waiting on it will send it again.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+/// Requests that take longer than this are dropped, so one slow client can't hold up
+/// everyone else.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Replays that take longer than this to verify are rejected. Clients wait a bit
+/// longer than this for an answer.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longer lines in the request head are rejected.
+const MAX_LINE_LEN: u64 = 8 * 1024;
+/// Bodies are a score, a name and a replay. Replays of even very long runs are well
+/// under this.
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+type Scores = HashMap<String, Vec<(f32, String)>>;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let scores_path = args.next();
+
+    let mut scores = Scores::new();
+
+    if let Some(path) = &scores_path {
+        if let Ok(file) = std::fs::File::open(path) {
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                let mut parts = line.splitn(4, '\t');
+                if let (Some(track), Some(score), Some(player)) =
+                    (parts.next(), parts.next(), parts.next())
+                {
+                    if let Ok(score) = score.parse() {
+                        add_score(&mut scores, track, score, player);
+                    }
+                }
+            }
+        }
+    }
+
+    let listener = TcpListener::bind(&addr).expect("failed to bind");
+    println!("listening on {}", addr);
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if stream.set_read_timeout(Some(TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+        {
+            continue;
+        }
+
+        if let Err(e) = handle(&mut stream, &mut scores, scores_path.as_deref()) {
+            eprintln!("error handling request: {}", e);
+            let _ = respond(&mut stream, "400 Bad Request", "");
+        }
+    }
+}
+
+fn handle(
+    stream: &mut TcpStream,
+    scores: &mut Scores,
+    scores_path: Option<&str>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    read_line(&mut reader, &mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        read_line(&mut reader, &mut header)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        return respond(stream, "413 Payload Too Large", "");
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+
+    let Some(track) = path.strip_prefix("/scores/") else {
+        return respond(stream, "404 Not Found", "");
+    };
+
+    match method {
+        "GET" => {
+            let mut response = String::new();
+            for (score, player) in scores.get(track).into_iter().flatten() {
+                response.push_str(&format!("{}\t{}\n", score, player));
+            }
+            respond(stream, "200 OK", &response)
+        }
+        "POST" => {
+            let mut parts = body.trim().splitn(3, '\t');
+            let (Some(score), Some(player)) = (parts.next(), parts.next()) else {
+                return respond(stream, "400 Bad Request", "");
+            };
+            let replay = parts.next().unwrap_or_default();
+            let Ok(score) = score.parse::<f32>() else {
+                return respond(stream, "400 Bad Request", "");
+            };
+            if !score.is_finite() || score <= 0. || player.contains(['\t', '\n']) {
+                return respond(stream, "400 Bad Request", "");
+            }
+
+            if scores
+                .get(track)
+                .is_some_and(|scores| scores.contains(&(score, player.to_string())))
+            {
+                println!("{}: {} {} was already added", track, player, score);
+                return respond(stream, "200 OK", "");
+            }
+
+            if let Err(e) = verify(track, score, replay) {
+                println!("{}: {} {} rejected: {}", track, player, score, e);
+                return respond(stream, "422 Unprocessable Entity", &e);
+            }
+
+            println!("{}: {} {}", track, player, score);
+            add_score(scores, track, score, player);
+
+            if let Some(path) = scores_path {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}\t{}\t{}\t{}", track, score, player, replay)?;
+            }
+
+            respond(stream, "200 OK", "")
+        }
+        _ => respond(stream, "405 Method Not Allowed", ""),
+    }
+}
+
+/// Checks that the replay is for this track and time, then has the game re-simulate it.
+fn verify(track: &str, score: f32, replay: &str) -> Result<(), String> {
+    let Ok(verifier) = std::env::var("COMBINE_RACERS_VERIFIER") else {
+        return Ok(());
+    };
+
+    // <format>:<track>:<tuning>:<time>:<frames>
+    let mut parts = replay.split(':').skip(1);
+    if parts.next() != Some(track) {
+        return Err("replay is for a different track".to_string());
+    }
+    if parts.nth(1).and_then(|time| time.parse::<f32>().ok()) != Some(score) {
+        return Err("replay is for a different time".to_string());
+    }
+
+    let path = std::env::temp_dir().join(format!("combine-racers-replay-{}", std::process::id()));
+    std::fs::write(&path, replay).map_err(|e| e.to_string())?;
+    let status = run_verifier(&verifier, &path);
+    let _ = std::fs::remove_file(&path);
+
+    match status {
+        Ok(Some(status)) if status.success() => Ok(()),
+        Ok(Some(_)) => Err("replay did not verify".to_string()),
+        Ok(None) => Err("replay took too long to verify".to_string()),
+        Err(e) => Err(format!("failed to run verifier: {}", e)),
+    }
+}
+
+/// Runs the verifier on the replay at `path`, killing it after `VERIFY_TIMEOUT`.
+fn run_verifier(
+    verifier: &str,
+    path: &std::path::Path,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let mut child = Command::new(verifier).arg(path).spawn()?;
+    let started = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if started.elapsed() > VERIFY_TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Reads a line of at most `MAX_LINE_LEN` bytes.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> std::io::Result<()> {
+    reader.take(MAX_LINE_LEN).read_line(line)?;
+    if !line.ends_with('\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "request line too long",
+        ));
+    }
+    Ok(())
+}
+
+fn add_score(scores: &mut Scores, track: &str, score: f32, player: &str) {
+    let track_scores = scores.entry(track.to_string()).or_default();
+    track_scores.push((score, player.to_string()));
+    track_scores.sort_by(|a, b| a.0.total_cmp(&b.0));
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,29 @@
+//! Checks a replay by running its race again without a window.
+//!
+//! ```text
+//! cargo run --release --bin verify_replay -- replay.txt
+//! ```
+//!
+//! Exits successfully only if the replay finishes in the time it claims.
+
+use bevy::prelude::*;
+use combine_racers::Replay;
+
+fn main() -> AppExit {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: verify_replay <replay file>");
+        return AppExit::error();
+    };
+
+    let replay = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|encoded| Replay::decode(&encoded).map_err(|e| e.to_string()));
+
+    match replay {
+        Ok(replay) => combine_racers::run(Some(replay)),
+        Err(e) => {
+            eprintln!("failed to read replay {}: {}", path, e);
+            AppExit::error()
+        }
+    }
+}
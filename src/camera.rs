@@ -0,0 +1,261 @@
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    photo_mode::photo_mode_inactive,
+    settings::{CameraSetting, ReducedMotionSetting},
+    zoom, AfterPhysics, BonkStatus, GameSet, GameState, MainCamera, Player, WheelsOnGround, Zoom,
+    LAVA,
+};
+
+/// How far ahead of the player we look, in seconds of travel at the current velocity.
+const LOOK_AHEAD_SECONDS: f32 = 0.6;
+const MAX_LOOK_AHEAD: f32 = 20.;
+/// How much further away the camera gets for each unit of speed.
+const SPEED_PULL_BACK: f32 = 0.8;
+/// How much further away the camera gets for each unit of height above the track.
+const ALTITUDE_PULL_BACK: f32 = 0.5;
+const MAX_ALTITUDE: f32 = 60.;
+/// How far ahead to look for ramps and drops.
+const RAMP_LOOK_AHEAD: f32 = 35.;
+/// How much of the difference in height between here and the track ahead to frame.
+const RAMP_FRAMING: f32 = 0.35;
+/// How quickly the camera catches up with where it wants to be.
+const SMOOTHING: f32 = 3.;
+/// Jump straight to the target rather than sweeping across the track after a reset.
+const SNAP_DISTANCE: f32 = 150.;
+
+/// How far the camera moves at full trauma.
+const MAX_SHAKE: f32 = 2.5;
+/// How much trauma wears off per second.
+const TRAUMA_DECAY: f32 = 1.2;
+/// How quickly the camera shakes.
+const SHAKE_FREQUENCY: f32 = 25.;
+/// The change in velocity that counts as a full strength bonk.
+const BONK_IMPACT: f32 = 40.;
+/// Landings softer than this don't shake the camera at all.
+const HEAVY_LANDING: f32 = 25.;
+/// The change in velocity that counts as a full strength landing.
+const LANDING_IMPACT: f32 = 80.;
+
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>()
+            .add_systems(PreUpdate, undo_shake)
+            .add_systems(
+                Update,
+                impact_trauma.run_if(in_state(GameState::Playing).and_then(photo_mode_inactive)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), lava_trauma)
+            .add_systems(OnEnter(GameState::Playing), clear_trauma)
+            .add_systems(
+                PostUpdate,
+                apply_shake
+                    .run_if(photo_mode_inactive)
+                    .after(AfterPhysics)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_systems(
+                PostUpdate,
+                dynamic_camera
+                    .after(GameSet::Movement)
+                    .after(zoom)
+                    .run_if(resource_equals(CameraSetting::Dynamic))
+                    .run_if(in_state(GameState::Playing).and_then(photo_mode_inactive))
+                    .in_set(AfterPhysics),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    dynamic_camera
+                        .run_if(resource_equals(CameraSetting::Dynamic))
+                        .run_if(in_state(GameState::Leaderboard)),
+                    dynamic_camera
+                        .run_if(resource_equals(CameraSetting::Dynamic))
+                        .run_if(in_state(GameState::GameOver)),
+                )
+                    .after(GameSet::Movement)
+                    .in_set(AfterPhysics),
+            );
+    }
+}
+
+/// Follows the player, leading in the direction of travel and pulling back with speed
+/// and altitude so that there's time to react to what's coming.
+fn dynamic_camera(
+    time: Res<Time>,
+    zoom: Res<Zoom>,
+    rapier_context: Res<RapierContext>,
+    player: Query<(&Transform, &Velocity), With<Player>>,
+    mut main_camera: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+    mut other_cameras: Query<&mut Transform, (With<Camera>, Without<MainCamera>, Without<Player>)>,
+) {
+    let Ok((player_transform, velocity)) = player.get_single() else {
+        return;
+    };
+    let Ok(mut camera_transform) = main_camera.get_single_mut() else {
+        return;
+    };
+
+    let position = player_transform.translation;
+    let travel = velocity.linvel.truncate();
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+
+    let look_ahead = (travel * LOOK_AHEAD_SECONDS).clamp_length_max(MAX_LOOK_AHEAD);
+
+    let altitude = rapier_context
+        .cast_ray(position, Vec3::NEG_Y, MAX_ALTITUDE, true, filter)
+        .map(|(_, toi)| toi)
+        .unwrap_or(MAX_ALTITUDE);
+
+    // Find the track a little way ahead, and show more of it if it's a ramp or a drop.
+    let mut ramp_offset = 0.;
+    if travel.x.abs() > 1. {
+        let ahead = Vec3::new(
+            position.x + travel.x.signum() * RAMP_LOOK_AHEAD,
+            position.y + MAX_ALTITUDE / 2.,
+            0.,
+        );
+        if let Some((_, toi)) =
+            rapier_context.cast_ray(ahead, Vec3::NEG_Y, MAX_ALTITUDE, true, filter)
+        {
+            let ground_ahead = ahead.y - toi;
+            ramp_offset = (ground_ahead - (position.y - altitude)) * RAMP_FRAMING;
+        }
+    }
+
+    let target = Vec3::new(
+        position.x + look_ahead.x,
+        position.y + look_ahead.y + ramp_offset,
+        zoom.current() + travel.length() * SPEED_PULL_BACK + altitude * ALTITUDE_PULL_BACK,
+    );
+
+    if camera_transform.translation.distance(target) > SNAP_DISTANCE {
+        camera_transform.translation = target;
+    } else {
+        let t = 1. - (-SMOOTHING * time.delta_seconds()).exp();
+        camera_transform.translation = camera_transform.translation.lerp(target, t);
+    }
+
+    // The background camera needs to move along with us.
+    for mut transform in &mut other_cameras {
+        transform.translation.x = camera_transform.translation.x;
+        transform.translation.y = camera_transform.translation.y;
+    }
+}
+
+/// Shakes the camera after big impacts. Trauma wears off over time, and the shake is
+/// proportional to its square so that small knocks are subtle.
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    /// The offset that was added to the camera this frame.
+    offset: Vec3,
+}
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0., 1.);
+    }
+}
+
+/// What the player was doing last frame, so that we can tell how hard they hit the track.
+#[derive(Default)]
+struct ImpactState {
+    velocity: Vec3,
+    airborne: bool,
+}
+
+fn impact_trauma(
+    query: Query<(Ref<BonkStatus>, &WheelsOnGround, &Velocity), With<Player>>,
+    reduced_motion: Res<ReducedMotionSetting>,
+    mut shake: ResMut<CameraShake>,
+    mut last: Local<ImpactState>,
+) {
+    let Ok((bonk, wheels, velocity)) = query.get_single() else {
+        return;
+    };
+
+    let impact = (velocity.linvel - last.velocity).length();
+    let landed = last.airborne && **wheels > 0;
+
+    last.velocity = velocity.linvel;
+    last.airborne = **wheels == 0;
+
+    if **reduced_motion {
+        return;
+    }
+
+    if bonk.is_changed() && !bonk.is_added() && **bonk {
+        shake.add_trauma(0.3 + 0.7 * (impact / BONK_IMPACT).min(1.));
+    } else if landed && impact > HEAVY_LANDING {
+        shake.add_trauma((impact - HEAVY_LANDING) / (LANDING_IMPACT - HEAVY_LANDING));
+    }
+}
+
+fn lava_trauma(
+    query: Query<&Transform, With<Player>>,
+    reduced_motion: Res<ReducedMotionSetting>,
+    mut shake: ResMut<CameraShake>,
+) {
+    if **reduced_motion {
+        return;
+    }
+
+    if query.iter().any(|transform| transform.translation.y < LAVA) {
+        shake.add_trauma(1.);
+    }
+}
+
+fn clear_trauma(mut shake: ResMut<CameraShake>) {
+    shake.trauma = 0.;
+}
+
+/// Takes back last frame's shake, so that it doesn't get mixed up with where the camera
+/// is supposed to be.
+fn undo_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    if shake.offset == Vec3::ZERO {
+        return;
+    }
+
+    for mut transform in &mut camera_query {
+        transform.translation -= shake.offset;
+    }
+
+    shake.offset = Vec3::ZERO;
+}
+
+fn apply_shake(
+    time: Res<Time>,
+    reduced_motion: Res<ReducedMotionSetting>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    if **reduced_motion {
+        shake.trauma = 0.;
+    }
+
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+    if shake.trauma <= 0. {
+        return;
+    }
+
+    // A few out of phase sine waves look random enough without needing a noise function.
+    let t = time.elapsed_seconds() * SHAKE_FREQUENCY;
+    let wobble = Vec3::new(
+        (t * 1.0).sin() + (t * 2.3 + 1.7).sin() * 0.5,
+        (t * 1.3 + 0.5).sin() + (t * 2.9 + 2.3).sin() * 0.5,
+        0.,
+    ) / 1.5;
+
+    let offset = wobble * shake.trauma * shake.trauma * MAX_SHAKE;
+
+    for mut transform in &mut camera_query {
+        transform.translation += offset;
+    }
+
+    shake.offset = offset;
+}
@@ -1,7 +1,13 @@
-use bevy::{audio::Volume, prelude::*};
+use bevy::prelude::*;
 use interpolation::Ease;
 
-use crate::{loading::AudioAssets, settings::SfxSetting, ui::TrickTextMarker, GameState, RaceTime};
+use crate::{
+    loading::AudioAssets,
+    localization::Localization,
+    mixer::{AudioBus, Mixer},
+    ui::{Palette, TrickTextMarker},
+    GameState, RaceTime,
+};
 
 pub struct CountdownPlugin;
 impl Plugin for CountdownPlugin {
@@ -38,16 +44,18 @@ fn update(
     mut text_query: Query<&mut Text, With<TrickTextMarker>>,
     mut race_time: ResMut<RaceTime>,
     game_audio: Res<AudioAssets>,
-    audio_setting: Res<SfxSetting>,
+    mixer: Res<Mixer>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
 ) {
     for mut timer in query.iter_mut() {
         if !timer.countdown.finished() {
             if timer.countdown.elapsed_secs() == 0.0 {
-                commands.spawn(AudioBundle {
-                    source: game_audio.three_two_one.clone(),
-                    settings: PlaybackSettings::DESPAWN
-                        .with_volume(Volume::new(**audio_setting as f32 / 100.)),
-                });
+                mixer.play(
+                    &mut commands,
+                    game_audio.three_two_one.clone(),
+                    AudioBus::Sfx,
+                );
             }
 
             timer.countdown.tick(time.delta());
@@ -57,12 +65,13 @@ fn update(
                     timer.countdown.fraction_remaining() * timer.countdown.duration().as_secs_f32();
 
                 text.sections[0].value = format!("{}", left.ceil());
-                text.sections[0].style.color = Color::srgba(1., 0., 0., Ease::cubic_out(left % 1.));
+                text.sections[0].style.color =
+                    palette.fade(palette.trick_text, Ease::cubic_out(left % 1.));
             }
 
             if timer.countdown.just_finished() {
                 for mut text in text_query.iter_mut() {
-                    text.sections[0].value = "GO!".to_string();
+                    text.sections[0].value = localization.get("countdown.go").to_string();
                 }
                 timer.go.reset();
                 race_time.unpause();
@@ -70,8 +79,10 @@ fn update(
         } else if !timer.go.finished() {
             timer.go.tick(time.delta());
             for mut text in text_query.iter_mut() {
-                text.sections[0].style.color =
-                    Color::srgba(1., 0., 0., Ease::cubic_out(timer.go.fraction_remaining()));
+                text.sections[0].style.color = palette.fade(
+                    palette.trick_text,
+                    Ease::cubic_out(timer.go.fraction_remaining()),
+                );
             }
         }
     }
@@ -0,0 +1,251 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{
+    audio::{AddAudioSource, Decodable, Source, Volume},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    mixer::{AudioBus, MixerChannel},
+    Boost, FinishedEvent, GameState, Player, PlayerInput, RaceTime, BOOST_SPEED_LIMIT,
+};
+
+const SAMPLE_RATE: u32 = 44_100;
+/// The engine's fundamental frequency when idling. Everything else is done by changing
+/// the playback speed.
+const ENGINE_HZ: f32 = 55.;
+const WHINE_HZ: f32 = 880.;
+/// How quickly pitch and volume follow the vehicle, per second.
+const RESPONSE: f32 = 6.;
+
+pub struct EngineSoundPlugin;
+impl Plugin for EngineSoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Synth>()
+            .init_resource::<EngineState>()
+            .add_systems(Startup, setup)
+            .add_systems(OnEnter(GameState::Playing), spawn)
+            .add_systems(Update, update.run_if(in_state(GameState::Playing)))
+            .add_systems(OnExit(GameState::Playing), despawn);
+    }
+}
+
+/// A looping sound made from a few harmonics of a single frequency.
+#[derive(Asset, TypePath, Clone)]
+struct Synth {
+    frequency: f32,
+    /// The relative volume of each harmonic, starting with the fundamental.
+    harmonics: Vec<f32>,
+    /// How much noise to mix in, for a bit of grit.
+    noise: f32,
+}
+
+struct SynthDecoder {
+    synth: Synth,
+    phase: f32,
+    seed: u32,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.phase = (self.phase + self.synth.frequency / SAMPLE_RATE as f32) % 1.;
+
+        let mut sample: f32 = self
+            .synth
+            .harmonics
+            .iter()
+            .enumerate()
+            .map(|(i, amplitude)| (self.phase * TAU * (i + 1) as f32).sin() * amplitude)
+            .sum();
+
+        // A tiny xorshift, as the noise doesn't need to be any good.
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        sample += (self.seed as f32 / u32::MAX as f32 * 2. - 1.) * self.synth.noise;
+
+        Some(sample * 0.5)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            synth: self.clone(),
+            phase: 0.,
+            seed: 0x2545_f491,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct EngineSounds {
+    engine: Handle<Synth>,
+    whine: Handle<Synth>,
+}
+
+#[derive(Component)]
+struct EngineSound;
+#[derive(Component)]
+struct BoostWhine;
+
+/// The current pitch and volume of the engine, which chase the vehicle's state.
+#[derive(Resource, Default)]
+struct EngineState {
+    pitch: f32,
+    volume: f32,
+    whine: f32,
+    /// The engine cuts out when the player crosses the finish line.
+    finished: bool,
+}
+
+fn setup(mut commands: Commands, mut synths: ResMut<Assets<Synth>>) {
+    commands.insert_resource(EngineSounds {
+        engine: synths.add(Synth {
+            frequency: ENGINE_HZ,
+            harmonics: vec![1., 0.6, 0.45, 0.3, 0.2, 0.1],
+            noise: 0.08,
+        }),
+        whine: synths.add(Synth {
+            frequency: WHINE_HZ,
+            harmonics: vec![1., 0.2],
+            noise: 0.02,
+        }),
+    });
+}
+
+fn spawn(mut commands: Commands, sounds: Res<EngineSounds>) {
+    commands.insert_resource(EngineState::default());
+
+    let settings = PlaybackSettings::LOOP.with_volume(Volume::ZERO);
+    let channel = || MixerChannel {
+        bus: AudioBus::Sfx,
+        volume: 0.,
+    };
+
+    commands.spawn((
+        AudioSourceBundle {
+            source: sounds.engine.clone(),
+            settings,
+        },
+        channel(),
+        EngineSound,
+    ));
+    commands.spawn((
+        AudioSourceBundle {
+            source: sounds.whine.clone(),
+            settings,
+        },
+        channel(),
+        BoostWhine,
+    ));
+}
+
+fn despawn(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<EngineSound>, With<BoostWhine>)>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update(
+    real_time: Res<Time<Real>>,
+    virtual_time: Res<Time<Virtual>>,
+    race_time: Res<RaceTime>,
+    player_query: Query<(&Velocity, &PlayerInput, &Boost), With<Player>>,
+    mut engine_query: Query<
+        (&mut MixerChannel, &AudioSink),
+        (With<EngineSound>, Without<BoostWhine>),
+    >,
+    mut whine_query: Query<
+        (&mut MixerChannel, &AudioSink),
+        (With<BoostWhine>, Without<EngineSound>),
+    >,
+    mut finished_events: EventReader<FinishedEvent>,
+    mut state: ResMut<EngineState>,
+) {
+    if finished_events.read().count() > 0 {
+        state.finished = true;
+    }
+    if state.finished {
+        for (mut channel, _) in engine_query.iter_mut().chain(whine_query.iter_mut()) {
+            channel.volume = 0.;
+        }
+        return;
+    }
+
+    // Keep quiet while the game is paused, for photos or by the pause menu.
+    for (_, sink) in engine_query.iter().chain(whine_query.iter()) {
+        if virtual_time.is_paused() {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+    if virtual_time.is_paused() {
+        return;
+    }
+
+    let Ok((velocity, input, boost)) = player_query.get_single() else {
+        return;
+    };
+
+    let speed = (velocity.linvel.length() / BOOST_SPEED_LIMIT).min(1.5);
+    let throttle = if input.forward || input.back { 1. } else { 0. };
+    // Idle quietly during the countdown.
+    let racing = !race_time.paused();
+    let boosting = racing && boost.remaining > 0.;
+
+    let (pitch, volume) = if racing {
+        (
+            0.7 + speed * 1.1 + throttle * 0.15,
+            0.25 + speed * 0.25 + throttle * 0.2,
+        )
+    } else {
+        (0.6, 0.15)
+    };
+    let whine = if boosting { 0.3 + speed * 0.2 } else { 0. };
+
+    let t = 1. - (-RESPONSE * real_time.delta_seconds()).exp();
+    if state.pitch == 0. {
+        state.pitch = pitch;
+    }
+    state.pitch += (pitch - state.pitch) * t;
+    state.volume += (volume - state.volume) * t;
+    state.whine += (whine - state.whine) * t;
+
+    for (mut channel, sink) in &mut engine_query {
+        sink.set_speed(state.pitch);
+        channel.volume = state.volume;
+    }
+    for (mut channel, sink) in &mut whine_query {
+        sink.set_speed(0.8 + state.pitch * 0.4);
+        channel.volume = state.whine;
+    }
+}
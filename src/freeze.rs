@@ -0,0 +1,40 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_rapier3d::prelude::RapierConfiguration;
+
+use crate::{Player, PlayerInput, RaceTime};
+
+/// Everything that's needed to freeze and unfreeze the game, for photo mode and the
+/// pause menu.
+#[derive(SystemParam)]
+pub struct Freeze<'w, 's> {
+    virtual_time: ResMut<'w, Time<Virtual>>,
+    race_time: ResMut<'w, RaceTime>,
+    rapier_config: ResMut<'w, RapierConfiguration>,
+    input_query: Query<'w, 's, &'static mut PlayerInput, With<Player>>,
+}
+
+impl Freeze<'_, '_> {
+    /// Stops time, physics and the player's input. Returns whether `RaceTime` was
+    /// running, to be passed back to [`Freeze::unfreeze`].
+    pub fn freeze(&mut self) -> bool {
+        let race_was_running = !self.race_time.paused();
+
+        self.virtual_time.pause();
+        self.race_time.pause();
+        self.rapier_config.physics_pipeline_active = false;
+
+        for mut input in &mut self.input_query {
+            *input = PlayerInput::default();
+        }
+
+        race_was_running
+    }
+
+    pub fn unfreeze(&mut self, race_was_running: bool) {
+        self.virtual_time.unpause();
+        if race_was_running {
+            self.race_time.unpause();
+        }
+        self.rapier_config.physics_pipeline_active = true;
+    }
+}
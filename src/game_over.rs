@@ -2,8 +2,12 @@ use bevy::prelude::*;
 use bevy_alt_ui_navigation_lite::prelude::*;
 
 use crate::{
+    history::{spawn_run_summary, LastRun},
     loading::GameAssets,
-    ui::{buttons, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, TITLE_TEXT},
+    localization::Localization,
+    settings::RunHistorySetting,
+    stats::{spawn_run_stats, RunStats},
+    ui::{buttons, Palette},
     GameState,
 };
 
@@ -30,11 +34,21 @@ enum GameOverButton {
     PlayAgain,
 }
 
-fn spawn(mut commands: Commands, assets: Res<GameAssets>) {
+fn spawn(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    last_run: Res<LastRun>,
+    history: Res<RunHistorySetting>,
+    run_stats: Res<RunStats>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    let font = localization.font(&assets);
+
     let title_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 60.0,
-        color: TITLE_TEXT,
+        color: palette.title_text,
     };
     let button_style = Style {
         width: Val::Px(250.0),
@@ -45,9 +59,9 @@ fn spawn(mut commands: Commands, assets: Res<GameAssets>) {
         ..default()
     };
     let button_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 30.0,
-        color: BUTTON_TEXT,
+        color: palette.button_text,
     };
 
     let root = commands
@@ -76,28 +90,38 @@ fn spawn(mut commands: Commands, assets: Res<GameAssets>) {
                 padding: UiRect::all(Val::Px(20.)),
                 ..default()
             },
-            background_color: CONTAINER_BACKGROUND.into(),
+            background_color: palette.container_background.into(),
             ..default()
         })
         .id();
 
     let title = commands
         .spawn(
-            TextBundle::from_section("Game Over", title_text_style).with_style(Style {
-                margin: UiRect {
-                    bottom: Val::Px(10.0),
+            TextBundle::from_section(localization.get("game_over.title"), title_text_style)
+                .with_style(Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
                     ..default()
-                },
-                ..default()
-            }),
+                }),
         )
         .id();
 
+    let record = spawn_run_summary(
+        &mut commands,
+        &font,
+        &localization,
+        &last_run,
+        &history,
+        &palette,
+    );
+
     let play_again = commands
         .spawn((
             ButtonBundle {
                 style: button_style,
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
@@ -106,17 +130,19 @@ fn spawn(mut commands: Commands, assets: Res<GameAssets>) {
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                "Play Again",
+                localization.get("common.play_again"),
                 button_text_style.clone(),
             ));
         })
         .id();
 
-    commands.entity(root).push_children(&[container]);
+    let stats = spawn_run_stats(&mut commands, &font, &localization, &run_stats, &palette);
+
+    commands.entity(root).push_children(&[container, stats]);
 
     commands
         .entity(container)
-        .push_children(&[title, play_again]);
+        .push_children(&[title, record, play_again]);
 }
 
 fn button_actions(
@@ -0,0 +1,285 @@
+use bevy::{
+    pbr::{DirectionalLightShadowMap, ShadowFilteringMethod},
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+    window::{PresentMode, PrimaryWindow, WindowMode, WindowRef, WindowResized},
+};
+
+use crate::{
+    settings::{
+        FrameRateLimitSetting, MsaaSetting, RenderScaleSetting, ResolutionSetting, ShadowSetting,
+        VsyncSetting, WindowModeSetting,
+    },
+    settings_menu::SettingsMenu,
+    GameState, Headless, MainCamera,
+};
+
+/// The layer for the camera that draws the scaled down game to the window. Nothing
+/// else is on it.
+const RENDER_SCALE_LAYER: usize = 2;
+
+pub struct GraphicsPlugin;
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            shadow_changed
+                .run_if(in_state(GameState::MainMenu).or_else(in_state(SettingsMenu::Open))),
+        )
+        .add_systems(
+            Update,
+            (
+                apply_window_settings.run_if(resource_exists::<WindowModeSetting>),
+                apply_msaa.run_if(resource_exists::<MsaaSetting>),
+                apply_render_scale.run_if(
+                    resource_exists::<RenderScaleSetting>
+                        .and_then(any_with_component::<MainCamera>),
+                ),
+            )
+                .run_if(not(resource_exists::<Headless>)),
+        );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            Last,
+            limit_frame_rate.run_if(
+                resource_exists::<FrameRateLimitSetting>.and_then(not(resource_exists::<Headless>)),
+            ),
+        );
+    }
+}
+
+/// The render target used when the render scale is below 100%.
+#[derive(Resource)]
+struct RenderScaleTarget {
+    image: Handle<Image>,
+    camera: Entity,
+    node: Entity,
+}
+
+#[derive(Component)]
+struct RenderScaleCamera;
+
+fn shadow_changed(
+    mut commands: Commands,
+    shadow_setting: Res<ShadowSetting>,
+    camera_query: Query<Entity, With<MainCamera>>,
+    mut light_query: Query<&mut DirectionalLight>,
+) {
+    // Do run when ShadowSetting is first added by SavePlugin
+    if !shadow_setting.is_changed() {
+        return;
+    }
+
+    let mut light = light_query.single_mut();
+    let camera_entity = camera_query.single();
+
+    match *shadow_setting {
+        ShadowSetting::None => {
+            light.shadows_enabled = false;
+            commands
+                .entity(camera_entity)
+                .insert(ShadowFilteringMethod::Hardware2x2);
+            commands.insert_resource(DirectionalLightShadowMap { size: 256 });
+        }
+        ShadowSetting::Low => {
+            light.shadows_enabled = true;
+            commands
+                .entity(camera_entity)
+                .insert(ShadowFilteringMethod::Hardware2x2);
+            commands.insert_resource(DirectionalLightShadowMap { size: 256 });
+        }
+        ShadowSetting::Medium => {
+            light.shadows_enabled = true;
+            commands
+                .entity(camera_entity)
+                .insert(ShadowFilteringMethod::Gaussian);
+            commands.insert_resource(DirectionalLightShadowMap { size: 512 });
+        }
+        ShadowSetting::High => {
+            light.shadows_enabled = true;
+            commands
+                .entity(camera_entity)
+                .insert(ShadowFilteringMethod::Gaussian);
+            commands.insert_resource(DirectionalLightShadowMap { size: 1024 });
+        }
+    }
+}
+
+/// Applies each window setting when it changes, so that resizing the window by hand
+/// isn't undone by toggling vsync.
+fn apply_window_settings(
+    window_mode: Res<WindowModeSetting>,
+    resolution: Res<ResolutionSetting>,
+    vsync: Res<VsyncSetting>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+
+    if window_mode.is_changed() {
+        window.mode = match *window_mode {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        };
+    }
+
+    if resolution.is_changed() {
+        window
+            .resolution
+            .set(resolution.width as f32, resolution.height as f32);
+    }
+
+    if vsync.is_changed() {
+        window.present_mode = if **vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+}
+
+fn apply_msaa(msaa_setting: Res<MsaaSetting>, mut msaa: ResMut<Msaa>) {
+    if !msaa_setting.is_changed() {
+        return;
+    }
+
+    *msaa = match *msaa_setting {
+        MsaaSetting::Off => Msaa::Off,
+        // WebGL2 only supports 4x
+        #[cfg(target_arch = "wasm32")]
+        _ => Msaa::Sample4,
+        #[cfg(not(target_arch = "wasm32"))]
+        MsaaSetting::Sample2 => Msaa::Sample2,
+        #[cfg(not(target_arch = "wasm32"))]
+        MsaaSetting::Sample4 => Msaa::Sample4,
+        #[cfg(not(target_arch = "wasm32"))]
+        MsaaSetting::Sample8 => Msaa::Sample8,
+    };
+}
+
+/// Below 100%, the game's cameras draw to a smaller image which is then stretched over
+/// the window behind the UI.
+fn apply_render_scale(
+    mut commands: Commands,
+    render_scale: Res<RenderScaleSetting>,
+    mut resized: EventReader<WindowResized>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<&mut Camera, Without<RenderScaleCamera>>,
+    target: Option<Res<RenderScaleTarget>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let resized = resized.read().count() > 0;
+    if !render_scale.is_changed() && !resized {
+        return;
+    }
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    if render_scale.scale() >= 1. {
+        let Some(target) = target else {
+            return;
+        };
+
+        info!("rendering at full resolution");
+
+        for mut camera in &mut camera_query {
+            camera.target = RenderTarget::Window(WindowRef::Primary);
+        }
+        commands.entity(target.camera).despawn_recursive();
+        commands.entity(target.node).despawn_recursive();
+        commands.remove_resource::<RenderScaleTarget>();
+
+        return;
+    }
+
+    let size = Extent3d {
+        width: ((window.physical_width() as f32 * render_scale.scale()) as u32).max(1),
+        height: ((window.physical_height() as f32 * render_scale.scale()) as u32).max(1),
+        ..default()
+    };
+
+    if let Some(target) = target {
+        if let Some(image) = images.get_mut(&target.image) {
+            image.resize(size);
+        }
+        return;
+    }
+
+    info!("rendering at {}x{}", size.width, size.height);
+
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Bgra8UnormSrgb,
+        default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    let image = images.add(image);
+
+    for mut camera in &mut camera_query {
+        camera.target = RenderTarget::Image(image.clone());
+    }
+
+    let camera = commands
+        .spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    order: 1,
+                    ..default()
+                },
+                ..default()
+            },
+            RenderLayers::layer(RENDER_SCALE_LAYER),
+            IsDefaultUiCamera,
+            RenderScaleCamera,
+        ))
+        .id();
+
+    let node = commands
+        .spawn(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            image: UiImage::new(image.clone()),
+            z_index: ZIndex::Global(i32::MIN),
+            ..default()
+        })
+        .id();
+
+    commands.insert_resource(RenderScaleTarget {
+        image,
+        camera,
+        node,
+    });
+}
+
+/// Sleeps at the end of the frame so that frames are at least as long as the limit.
+#[cfg(not(target_arch = "wasm32"))]
+fn limit_frame_rate(
+    limit: Res<FrameRateLimitSetting>,
+    mut last_frame: Local<Option<std::time::Instant>>,
+) {
+    if let (Some(fps), Some(last_frame)) = (**limit, *last_frame) {
+        let frame_time = std::time::Duration::from_secs_f64(1. / fps as f64);
+        let elapsed = last_frame.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+
+    *last_frame = Some(std::time::Instant::now());
+}
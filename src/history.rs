@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+
+use bevy::{prelude::*, utils::SystemTime};
+
+use crate::{
+    loading::TRACK_NAME,
+    localization::Localization,
+    settings::{RunHistorySetting, RunRecord},
+    splits::Splits,
+    stats::RunStats,
+    ui::Palette,
+    FinishedEvent, GameState, RaceTime,
+};
+
+/// The number of runs we keep around for each track.
+const MAX_RUNS_PER_TRACK: usize = 100;
+
+pub struct HistoryPlugin;
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastRun>()
+            .add_systems(OnEnter(GameState::Playing), clear_last_run)
+            .add_systems(Update, record_run.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// The run that was just finished, and the personal best that it was up against.
+#[derive(Resource, Default)]
+pub struct LastRun(pub Option<(RunRecord, Option<RunRecord>)>);
+
+fn clear_last_run(mut last_run: ResMut<LastRun>) {
+    last_run.0 = None;
+}
+
+fn record_run(
+    mut events: EventReader<FinishedEvent>,
+    race_time: Res<RaceTime>,
+    run_stats: Res<RunStats>,
+    splits: Res<Splits>,
+    mut history: ResMut<RunHistorySetting>,
+    mut last_run: ResMut<LastRun>,
+) {
+    // The finish line may be crossed by several colliders, but only record one run.
+    if events.read().count() == 0 || last_run.0.is_some() {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let record = RunRecord {
+        time: race_time.elapsed_secs(),
+        timestamp,
+        tricks: run_stats.tricks,
+        bonks: run_stats.bonks,
+        splits: splits.times.clone(),
+    };
+
+    let best = history.personal_best(TRACK_NAME).cloned();
+
+    info!("recording run {:?} (best: {:?})", record, best);
+
+    let runs = history.entry(TRACK_NAME.to_string()).or_default();
+    runs.push(record.clone());
+    if runs.len() > MAX_RUNS_PER_TRACK {
+        // Throw away the oldest run, unless it's the one we'd want to keep.
+        let best_index = runs
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal))
+            .map(|(i, _)| i);
+        let remove = if best_index == Some(0) { 1 } else { 0 };
+        runs.remove(remove);
+    }
+
+    last_run.0 = Some((record, best));
+}
+
+/// Spawns a text node describing the last run and how it compares to the
+/// personal best.
+pub fn spawn_run_summary(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    localization: &Localization,
+    last_run: &LastRun,
+    history: &RunHistorySetting,
+    palette: &Palette,
+) -> Entity {
+    let record_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.title_text,
+    };
+
+    let mut sections = vec![];
+
+    match &last_run.0 {
+        Some((run, best)) => {
+            sections.push(TextSection::new(
+                format!(
+                    "{}\n",
+                    localization.format("history.time", &[&format!("{:.3}", run.time)])
+                ),
+                record_text_style.clone(),
+            ));
+
+            match best {
+                Some(best) => {
+                    let delta = run.time - best.time;
+
+                    sections.push(TextSection::new(
+                        format!(
+                            "{} ",
+                            localization
+                                .format("history.personal_best", &[&format!("{:.3}", best.time)])
+                        ),
+                        record_text_style.clone(),
+                    ));
+                    sections.push(TextSection::new(
+                        format!("({:+.3})", delta),
+                        TextStyle {
+                            color: if delta < 0. {
+                                palette.faster_text
+                            } else {
+                                palette.slower_text
+                            },
+                            ..record_text_style.clone()
+                        },
+                    ));
+                    if delta < 0. {
+                        sections.push(TextSection::new(
+                            format!("\n{}", localization.get("history.new_personal_best")),
+                            TextStyle {
+                                color: palette.faster_text,
+                                ..record_text_style.clone()
+                            },
+                        ));
+                    }
+                }
+                None => {
+                    sections.push(TextSection::new(
+                        localization.get("history.new_personal_best"),
+                        TextStyle {
+                            color: palette.faster_text,
+                            ..record_text_style.clone()
+                        },
+                    ));
+                }
+            }
+        }
+        None => {
+            if let Some(best) = history.personal_best(TRACK_NAME) {
+                sections.push(TextSection::new(
+                    localization.format("history.personal_best", &[&format!("{:.3}", best.time)]),
+                    record_text_style.clone(),
+                ));
+            }
+        }
+    }
+
+    commands
+        .spawn(
+            TextBundle::from_sections(sections)
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+        )
+        .id()
+}
@@ -2,53 +2,231 @@ use std::cmp::Ordering;
 
 use bevy::prelude::*;
 use bevy_alt_ui_navigation_lite::prelude::*;
-use bevy_jornet::{JornetEvent, JornetPlugin, Leaderboard};
 
 use crate::{
+    history::{spawn_run_summary, LastRun},
     loading::GameAssets,
+    localization::Localization,
     random_name::random_name,
-    settings::LeaderboardSetting,
-    ui::{buttons, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON, OUR_SCORE_TEXT, TITLE_TEXT},
-    GameState, RaceTime,
+    replay::{Replay, ReplayRecorder},
+    settings::{PendingScore, PendingScoresSetting, PlayerNameSetting, RunHistorySetting},
+    stats::{spawn_run_stats, RunStats},
+    ui::{buttons, Palette},
+    GameState, RaceTime, TUNING_VERSION,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+mod http;
+mod jornet;
+mod local;
+
 pub struct LeaderboardPlugin;
 impl Plugin for LeaderboardPlugin {
     fn build(&self, app: &mut App) {
-        if let Some((id, key)) = get_leaderboard_credentials() {
-            app.init_resource::<ScoreSaved>()
-                .init_resource::<Refreshing>()
-                .init_resource::<RefreshTimer>()
-                .add_plugins(JornetPlugin::with_leaderboard(id, key))
-                .add_systems(Update, save_leaderboard_setting)
-                .add_systems(OnEnter(GameState::MainMenu), create_player)
-                .add_systems(
-                    OnEnter(GameState::Leaderboard),
-                    (save_score, spawn_leaderboard),
-                )
-                .add_systems(
-                    Update,
-                    (
-                        initiate_refresh,
-                        update_leaderboard,
-                        button_actions,
-                        buttons.after(NavRequestSystem),
-                    )
-                        .run_if(in_state(GameState::Leaderboard)),
+        app.add_event::<LeaderboardEvent>()
+            .init_resource::<ScoreSaved>()
+            .init_resource::<Refreshing>()
+            .init_resource::<RefreshTimer>()
+            .init_resource::<LeaderboardError>()
+            .init_resource::<ScoreQueue>()
+            .init_resource::<ConnectionStatus>()
+            .add_event::<RetryLeaderboard>()
+            .init_resource::<LeaderboardScores>()
+            .init_resource::<PageStart>();
+
+        match select_backend() {
+            BackendKind::Jornet(id, key) => {
+                info!("using the jornet leaderboard.");
+                app.add_plugins(jornet::JornetBackendPlugin { id, key });
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            BackendKind::Http(url) => {
+                info!("using the http leaderboard at {}.", url);
+                app.add_plugins(http::HttpBackendPlugin { url });
+            }
+            BackendKind::Local => {
+                info!("using the local leaderboard.");
+                app.add_plugins(local::LocalBackendPlugin);
+            }
+        }
+
+        app.add_systems(Update, (track_connection, send_timeout))
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                drop_outdated_scores.run_if(run_once()),
+            )
+            .add_systems(
+                OnEnter(GameState::Leaderboard),
+                (queue_score, reset_status, spawn_leaderboard),
+            )
+            .add_systems(
+                Update,
+                (
+                    render_scores,
+                    queue_text,
+                    (leaderboard_status, status_ui).chain(),
+                    button_actions,
+                    buttons.after(NavRequestSystem),
                 )
-                .add_systems(OnExit(GameState::Leaderboard), cleanup);
+                    .run_if(in_state(GameState::Leaderboard)),
+            )
+            .add_systems(OnExit(GameState::Leaderboard), cleanup);
+    }
+}
+
+/// A place to keep scores.
+///
+/// Each backend's plugin inserts the backend as a resource, calls [`add_backend_systems`],
+/// and reports the results of requests by sending [`LeaderboardEvent`]s.
+pub trait LeaderboardBackend: Resource {
+    /// Starts playing as the player with the given name, creating them if needed.
+    fn login(&mut self, name: &str);
+    /// The name of the player we're playing as, once logged in.
+    fn player_name(&self) -> Option<String>;
+    /// Submits a score along with the encoded [`Replay`](crate::replay::Replay) of the
+    /// run that set it. Backends that can't check the replay don't send it.
+    ///
+    /// `send` numbers this attempt, and is passed back in the [`LeaderboardEvent`] that
+    /// answers it.
+    fn submit_score(&mut self, send: u32, score: f32, replay: &str);
+    /// Requests the latest scores, which are available from [`LeaderboardBackend::scores`]
+    /// after a [`LeaderboardEvent::RefreshSuccess`].
+    fn fetch_scores(&mut self);
+    fn scores(&self) -> Vec<Score>;
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardEvent {
+    CreatePlayerSuccess,
+    CreatePlayerFailure,
+    /// The score sent by the numbered attempt made it onto the leaderboard.
+    SendScoreSuccess(u32),
+    SendScoreFailure(u32),
+    /// The leaderboard turned the score down, e.g. because its replay didn't verify.
+    /// Sending it again won't help.
+    SendScoreRejected(u32),
+    /// The backend didn't answer in time. Sent by [`LeaderboardPlugin`], not backends.
+    SendScoreTimeout(u32),
+    RefreshSuccess,
+    RefreshFailure,
+}
+
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub player: String,
+    pub score: f32,
+}
+
+enum BackendKind {
+    Jornet(&'static str, &'static str),
+    #[cfg(not(target_arch = "wasm32"))]
+    Http(String),
+    Local,
+}
+
+/// Picks a backend based on the environment at run time and at build time.
+///
+/// `COMBINE_RACERS_LEADERBOARD` may be set to `local`, `jornet`, or the url of a
+/// leaderboard server. Otherwise, we'll use jornet if credentials were provided at
+/// build time.
+fn select_backend() -> BackendKind {
+    #[cfg(not(target_arch = "wasm32"))]
+    let choice = std::env::var("COMBINE_RACERS_LEADERBOARD")
+        .ok()
+        .or(option_env!("COMBINE_RACERS_LEADERBOARD").map(|s| s.to_string()));
+    #[cfg(target_arch = "wasm32")]
+    let choice = option_env!("COMBINE_RACERS_LEADERBOARD").map(|s| s.to_string());
+
+    let credentials = get_leaderboard_credentials();
+
+    match (choice.as_deref(), credentials) {
+        (Some("local"), _) => BackendKind::Local,
+        #[cfg(not(target_arch = "wasm32"))]
+        (Some(url), _) if url.starts_with("http://") => BackendKind::Http(url.to_string()),
+        (Some("jornet") | None, Some((id, key))) => BackendKind::Jornet(id, key),
+        (Some(other), _) => {
+            warn!(
+                "leaderboard {:?} is unavailable. using the local leaderboard.",
+                other
+            );
+            BackendKind::Local
         }
+        (None, None) => BackendKind::Local,
     }
 }
 
+/// Adds the systems that drive a [`LeaderboardBackend`] through a race.
+pub fn add_backend_systems<B: LeaderboardBackend>(app: &mut App) {
+    app.add_systems(OnEnter(GameState::MainMenu), create_player::<B>)
+        .add_systems(
+            Update,
+            (
+                send_queued_scores::<B>.after(send_timeout),
+                (initiate_refresh::<B>, update_leaderboard::<B>, retry::<B>)
+                    .run_if(in_state(GameState::Leaderboard)),
+            ),
+        );
+}
+
 #[derive(Resource, Default, Deref, DerefMut)]
 struct ScoreSaved(bool);
 
+/// How long to wait for a response to a score before trying again. Longer than a
+/// leaderboard server might take to verify the replay.
+const SEND_TIMEOUT: f32 = 120.;
+/// The longest we'll wait between attempts to send a score.
+const MAX_RETRY_DELAY: f32 = 60.;
+/// How many unsent scores to keep in the save file.
+const MAX_PENDING_SCORES: usize = 10;
+
+/// Keeps track of our attempts to send the scores in `PendingScoresSetting`.
+#[derive(Resource)]
+struct ScoreQueue {
+    /// The attempt we're waiting on.
+    in_flight: Option<u32>,
+    /// Every attempt at sending the first pending score. One that timed out may still
+    /// succeed after we've sent the score again.
+    attempts: Vec<u32>,
+    next_send: u32,
+    failures: u32,
+    /// Counts down to the next attempt, or to giving up on the current one.
+    timer: Timer,
+}
+impl Default for ScoreQueue {
+    fn default() -> Self {
+        Self {
+            in_flight: None,
+            attempts: vec![],
+            next_send: 0,
+            failures: 0,
+            timer: Timer::from_seconds(0., TimerMode::Once),
+        }
+    }
+}
+
+/// How signing in and sending scores went. Both happen away from the leaderboard
+/// screen too, so this keeps track of them for when it's shown.
+#[derive(Resource, Default)]
+struct ConnectionStatus {
+    sign_in: SignIn,
+    /// Why the last score wasn't sent, as a localization key.
+    send_error: Option<&'static str>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum SignIn {
+    #[default]
+    Pending,
+    SignedIn,
+    Failed,
+}
+
+/// How long we'll wait for the leaderboard before giving up.
 #[derive(Resource, Deref, DerefMut)]
 struct RefreshTimer(Timer);
 impl Default for RefreshTimer {
     fn default() -> Self {
-        Self(Timer::from_seconds(3., TimerMode::Once))
+        Self(Timer::from_seconds(15., TimerMode::Once))
     }
 }
 
@@ -59,18 +237,62 @@ impl Default for Refreshing {
         Self(true)
     }
 }
+/// Why the leaderboard couldn't be shown, as a localization key.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct LeaderboardError(Option<&'static str>);
+
+#[derive(Event)]
+struct RetryLeaderboard;
+
 #[derive(Component)]
 struct LoadingText;
 #[derive(Component)]
+struct ErrorButtonsContainer;
+#[derive(Component)]
 struct LeaderboardMarker;
 #[derive(Component)]
 struct ScoresContainer;
 
+#[derive(Component)]
+struct PageText;
+#[derive(Component)]
+struct QueueText;
+
+/// The number of scores shown at a time.
+const PAGE_SIZE: usize = 10;
+
+#[derive(Resource, Default)]
+struct LeaderboardScores {
+    /// `(player, score)`, from best to worst.
+    scores: Vec<(String, f32)>,
+    /// The index of our score in `scores`.
+    ours: Option<usize>,
+}
+impl LeaderboardScores {
+    /// The start of a page with our score in the middle.
+    fn around_us(&self) -> usize {
+        let Some(ours) = self.ours else {
+            return 0;
+        };
+
+        ours.saturating_sub(PAGE_SIZE / 2)
+            .min(self.scores.len().saturating_sub(PAGE_SIZE))
+    }
+}
+
+/// The index of the first score on the current page.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct PageStart(usize);
+
 #[derive(Component)]
 struct PlayAgainButton;
 #[derive(Component)]
 enum LeaderboardButton {
     PlayAgain,
+    Retry,
+    PreviousPage,
+    AroundMe,
+    NextPage,
 }
 
 pub fn get_leaderboard_credentials() -> Option<(&'static str, &'static str)> {
@@ -84,164 +306,511 @@ pub fn get_leaderboard_credentials() -> Option<(&'static str, &'static str)> {
     }
 }
 
-fn save_leaderboard_setting(
-    mut leaderboard_setting: ResMut<LeaderboardSetting>,
-    mut events: EventReader<JornetEvent>,
-    leaderboard: Res<Leaderboard>,
+fn initiate_refresh<B: LeaderboardBackend>(
+    mut backend: ResMut<B>,
+    mut events: EventReader<LeaderboardEvent>,
+) {
+    // If sending failed, the score is queued up for later. Show the leaderboard anyway.
+    if !events.read().any(|e| {
+        matches!(
+            *e,
+            LeaderboardEvent::SendScoreSuccess(_)
+                | LeaderboardEvent::SendScoreFailure(_)
+                | LeaderboardEvent::SendScoreRejected(_)
+                | LeaderboardEvent::SendScoreTimeout(_)
+        )
+    }) {
+        return;
+    }
+
+    info!("score sending finished. refreshing leaderboard.");
+
+    backend.fetch_scores();
+}
+
+/// Sends the scores in `PendingScoresSetting` one at a time, backing off when sending fails.
+fn send_queued_scores<B: LeaderboardBackend>(
+    time: Res<Time>,
+    mut backend: ResMut<B>,
+    mut pending_scores: ResMut<PendingScoresSetting>,
+    mut queue: ResMut<ScoreQueue>,
+    mut events: EventReader<LeaderboardEvent>,
+) {
+    queue.timer.tick(time.delta());
+
+    for event in events.read() {
+        match *event {
+            // A rejected score would be rejected again, and hold up the ones behind it.
+            LeaderboardEvent::SendScoreSuccess(send)
+            | LeaderboardEvent::SendScoreRejected(send) => {
+                // Answers to attempts at scores we're already done with are stale.
+                if !queue.attempts.contains(&send) {
+                    continue;
+                }
+
+                if !pending_scores.is_empty() {
+                    pending_scores.remove(0);
+                }
+                queue.in_flight = None;
+                queue.attempts.clear();
+                queue.failures = 0;
+                queue.timer = Timer::from_seconds(0., TimerMode::Once);
+            }
+            LeaderboardEvent::SendScoreFailure(send) | LeaderboardEvent::SendScoreTimeout(send) => {
+                if queue.in_flight != Some(send) {
+                    continue;
+                }
+
+                queue.in_flight = None;
+                queue.failures += 1;
+
+                let delay = 2f32.powi(queue.failures as i32).min(MAX_RETRY_DELAY);
+                queue.timer = Timer::from_seconds(delay, TimerMode::Once);
+
+                warn!(
+                    "sending score failed {} time(s). retrying in {}s.",
+                    queue.failures, delay
+                );
+            }
+            _ => {}
+        }
+    }
+
+    if queue.in_flight.is_some() || !queue.timer.finished() {
+        return;
+    }
+
+    let Some(pending) = pending_scores.first() else {
+        return;
+    };
+
+    if backend.player_name().is_none() {
+        return;
+    }
+
+    info!(
+        "sending score {}. player is: {:?}",
+        pending.score,
+        backend.player_name()
+    );
+    let send = queue.next_send;
+    queue.next_send = send.wrapping_add(1);
+    backend.submit_score(send, pending.score, &pending.replay);
+
+    queue.in_flight = Some(send);
+    queue.attempts.push(send);
+    queue.timer = Timer::from_seconds(SEND_TIMEOUT, TimerMode::Once);
+}
+
+/// Drops queued scores that were set with different tuning. Their replays can't be
+/// verified anymore, so the leaderboard would only reject them.
+fn drop_outdated_scores(mut pending_scores: ResMut<PendingScoresSetting>) {
+    let current = |pending: &PendingScore| {
+        Replay::decode(&pending.replay).is_ok_and(|replay| replay.tuning == TUNING_VERSION)
+    };
+
+    if pending_scores.iter().all(current) {
+        return;
+    }
+
+    let queued = pending_scores.len();
+    pending_scores.retain(current);
+    warn!(
+        "dropped {} queued score(s) from an older version of the game.",
+        queued - pending_scores.len()
+    );
+}
+
+/// Gives up on a score that the backend never answered, as if sending it had failed.
+fn send_timeout(queue: Res<ScoreQueue>, mut events: EventWriter<LeaderboardEvent>) {
+    if let Some(send) = queue.in_flight.filter(|_| queue.timer.finished()) {
+        warn!("sending score timed out.");
+        events.send(LeaderboardEvent::SendScoreTimeout(send));
+    }
+}
+
+fn track_connection(
+    mut status: ResMut<ConnectionStatus>,
+    mut events: EventReader<LeaderboardEvent>,
+) {
+    for event in events.read() {
+        match event {
+            LeaderboardEvent::CreatePlayerSuccess => status.sign_in = SignIn::SignedIn,
+            LeaderboardEvent::CreatePlayerFailure => {
+                warn!("failed to sign in to the leaderboard.");
+                status.sign_in = SignIn::Failed;
+            }
+            LeaderboardEvent::SendScoreSuccess(_) => status.send_error = None,
+            LeaderboardEvent::SendScoreFailure(_) => {
+                status.send_error = Some("leaderboard.send_failed");
+            }
+            LeaderboardEvent::SendScoreRejected(_) => {
+                status.send_error = Some("leaderboard.send_rejected");
+            }
+            LeaderboardEvent::SendScoreTimeout(_) => {
+                status.send_error = Some("leaderboard.send_timed_out");
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_leaderboard<B: LeaderboardBackend>(
+    backend: Res<B>,
+    time: Res<RaceTime>,
+    player_name: Res<PlayerNameSetting>,
+    mut leaderboard_scores: ResMut<LeaderboardScores>,
+    mut page_start: ResMut<PageStart>,
+    mut events: EventReader<LeaderboardEvent>,
 ) {
     if !events
         .read()
-        .any(|e| matches!(*e, JornetEvent::CreatePlayerSuccess))
+        .any(|e| matches!(*e, LeaderboardEvent::RefreshSuccess))
     {
         return;
     }
 
-    if let Some(player) = leaderboard.get_player() {
-        leaderboard_setting.0 = Some(player.clone());
+    info!("update_leaderboard");
+
+    if let Some(name) = &**player_name {
+        let scores = backend
+            .scores()
+            .into_iter()
+            .map(|score| (score.player, score.score))
+            .collect();
+
+        set_scores(
+            &mut leaderboard_scores,
+            &mut page_start,
+            scores,
+            name,
+            time.elapsed_secs(),
+        );
     }
 }
 
-fn initiate_refresh(leaderboard: Res<Leaderboard>, mut events: EventReader<JornetEvent>) {
-    if !events
-        .read()
-        .any(|e| matches!(*e, JornetEvent::SendScoreSuccess))
+/// Sorts `(player, score)` rows for display and scrolls to our score.
+fn set_scores(
+    leaderboard_scores: &mut LeaderboardScores,
+    page_start: &mut PageStart,
+    mut scores: Vec<(String, f32)>,
+    player_name: &str,
+    time: f32,
+) {
+    scores.sort_unstable_by(|s1, s2| s1.1.partial_cmp(&s2.1).unwrap_or(Ordering::Equal));
+
+    // If our score didn't make it into the list (it may not have been accepted yet, or the
+    // list may be truncated), slot it in where it would be.
+    let ours = match scores
+        .iter()
+        .position(|(name, score)| name == player_name && *score == time)
     {
+        Some(ours) => ours,
+        None => {
+            let ours = scores.partition_point(|(_, score)| *score <= time);
+            scores.insert(ours, (player_name.to_string(), time));
+            ours
+        }
+    };
+
+    leaderboard_scores.scores = scores;
+    leaderboard_scores.ours = Some(ours);
+
+    **page_start = leaderboard_scores.around_us();
+}
+
+fn reset_status(
+    mut refreshing: ResMut<Refreshing>,
+    mut refresh_timer: ResMut<RefreshTimer>,
+    mut error: ResMut<LeaderboardError>,
+) {
+    **refreshing = true;
+    refresh_timer.reset();
+    **error = None;
+}
+
+fn leaderboard_status(
+    time: Res<Time>,
+    status: Res<ConnectionStatus>,
+    mut refreshing: ResMut<Refreshing>,
+    mut refresh_timer: ResMut<RefreshTimer>,
+    mut error: ResMut<LeaderboardError>,
+    mut events: EventReader<LeaderboardEvent>,
+) {
+    for event in events.read() {
+        match event {
+            LeaderboardEvent::RefreshSuccess => {
+                **refreshing = false;
+                **error = None;
+            }
+            LeaderboardEvent::RefreshFailure => {
+                **refreshing = false;
+                **error = Some("leaderboard.load_failed");
+            }
+            _ => {}
+        }
+    }
+
+    if !**refreshing {
         return;
     }
 
-    info!("score sending succeeded. refreshing leaderboard.");
+    // Without a player, no score will be sent and the leaderboard won't be refreshed.
+    if status.sign_in == SignIn::Failed {
+        **refreshing = false;
+        **error = Some("leaderboard.sign_in_failed");
+        return;
+    }
 
-    leaderboard.refresh_leaderboard();
+    refresh_timer.tick(time.delta());
+    if refresh_timer.just_finished() {
+        warn!("timed out waiting for the leaderboard.");
+        **refreshing = false;
+        **error = Some("leaderboard.not_responding");
+    }
 }
 
-fn update_leaderboard(
+/// Shows "Loading..." or an error message with a retry button, depending on the status
+/// of the leaderboard.
+fn status_ui(
     mut commands: Commands,
-    leaderboard: Res<Leaderboard>,
-    time: Res<RaceTime>,
-    container_query: Query<Entity, With<ScoresContainer>>,
-    loading_text_query: Query<Entity, With<LoadingText>>,
+    refreshing: Res<Refreshing>,
+    error: Res<LeaderboardError>,
+    pending_scores: Res<PendingScoresSetting>,
+    mut text_query: Query<(&mut Text, &mut Style), With<LoadingText>>,
+    container_query: Query<(Entity, Option<&Children>), With<ErrorButtonsContainer>>,
     assets: Res<GameAssets>,
-    mut events: EventReader<JornetEvent>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
 ) {
-    if !events
-        .read()
-        .any(|e| matches!(*e, JornetEvent::RefreshLeaderboardSuccess))
-    {
+    if !refreshing.is_changed() && !error.is_changed() {
         return;
     }
 
-    info!("update_leaderboard");
+    for (mut text, mut style) in text_query.iter_mut() {
+        let value = match (**error, **refreshing) {
+            (Some(error), _) if !pending_scores.is_empty() => {
+                localization.format("leaderboard.sent_later", &[&localization.get(error)])
+            }
+            (Some(error), _) => localization.get(error).to_string(),
+            (None, true) => localization.get("leaderboard.loading").to_string(),
+            (None, false) => String::new(),
+        };
 
-    for entity in loading_text_query.iter() {
-        // I am not sure why this needs to be despawn_recursive, but we panic without it.
-        // Is despawn_recursive better named `hierarchy_aware_despawn` or something? The
-        // LoadingText itself has no children...
-        commands.entity(entity).despawn_recursive();
+        style.display = if value.is_empty() {
+            Display::None
+        } else {
+            Display::Flex
+        };
+        text.sections[0].value = value;
     }
 
-    if let Some(player) = leaderboard.get_player() {
-        let container = container_query.single();
-        commands.entity(container).despawn_descendants();
+    for (entity, children) in container_query.iter() {
+        let has_button = children.is_some_and(|children| !children.is_empty());
 
-        let mut leaderboard = leaderboard.get_leaderboard();
-        leaderboard
-            .sort_unstable_by(|s1, s2| s1.score.partial_cmp(&s2.score).unwrap_or(Ordering::Equal));
-        leaderboard.truncate(10);
+        if error.is_none() {
+            if has_button {
+                commands.entity(entity).despawn_descendants();
+            }
+            continue;
+        }
 
-        let has_us = leaderboard
-            .iter()
-            .any(|score| player.name == score.player && score.score == time.elapsed_secs());
-
-        for (i, score) in leaderboard.iter().enumerate() {
-            // When we have a fresh leaderboard (when not refreshing), we assume
-            // that our score would be included if it were high enough. So if we
-            // haven't already displayed our score, toss it in at the last position.
-            let (display_score, display_name, is_us, rank) =
-                if !has_us && i == leaderboard.len() - 1 {
-                    (time.elapsed_secs(), &player.name, true, "?".to_string())
-                } else {
-                    let is_us = player.name == score.player && score.score == time.elapsed_secs();
-
-                    (score.score, &score.player, is_us, format!("{}", i + 1))
-                };
-
-            let row = commands
-                .spawn(NodeBundle {
-                    style: Style {
-                        height: Val::Px(30.),
+        if has_button {
+            continue;
+        }
+
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(250.0),
+                            height: Val::Px(45.0),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: palette.normal_button.into(),
                         ..default()
                     },
+                    Focusable::default(),
+                    LeaderboardButton::Retry,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        localization.get("leaderboard.retry"),
+                        TextStyle {
+                            font: localization.font(&assets),
+                            font_size: 30.0,
+                            color: palette.button_text,
+                        },
+                    ));
+                });
+        });
+    }
+}
+
+fn retry<B: LeaderboardBackend>(
+    mut backend: ResMut<B>,
+    player_name: Res<PlayerNameSetting>,
+    mut events: EventReader<RetryLeaderboard>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    info!("retrying leaderboard.");
+
+    if backend.player_name().is_none() {
+        if let Some(name) = &**player_name {
+            backend.login(name);
+        }
+    }
+
+    backend.fetch_scores();
+}
+
+fn render_scores(
+    mut commands: Commands,
+    leaderboard_scores: Res<LeaderboardScores>,
+    page_start: Res<PageStart>,
+    container_query: Query<Entity, With<ScoresContainer>>,
+    mut page_text_query: Query<&mut Text, With<PageText>>,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    if !leaderboard_scores.is_changed() && !page_start.is_changed() {
+        return;
+    }
+
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+
+    commands.entity(container).despawn_descendants();
+
+    let total = leaderboard_scores.scores.len();
+    if total == 0 {
+        return;
+    }
+
+    let end = (**page_start + PAGE_SIZE).min(total);
+
+    for mut text in page_text_query.iter_mut() {
+        text.sections[0].value =
+            localization.format("leaderboard.page", &[&(**page_start + 1), &end, &total]);
+    }
+
+    for (i, (name, score)) in leaderboard_scores
+        .scores
+        .iter()
+        .enumerate()
+        .take(end)
+        .skip(**page_start)
+    {
+        let is_us = leaderboard_scores.ours == Some(i);
+
+        let row = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    height: Val::Px(30.),
                     ..default()
-                })
-                .id();
+                },
+                ..default()
+            })
+            .id();
 
-            let rank_text = commands
-                .spawn(TextBundle {
-                    text: Text::from_section(
-                        rank,
-                        TextStyle {
-                            font: assets.font.clone(),
-                            font_size: 30.,
-                            color: if is_us { OUR_SCORE_TEXT } else { TITLE_TEXT },
+        let rank_text = commands
+            .spawn(TextBundle {
+                text: Text::from_section(
+                    format!("{}", i + 1),
+                    TextStyle {
+                        font: localization.font(&assets),
+                        font_size: 30.,
+                        color: if is_us {
+                            palette.our_score_text
+                        } else {
+                            palette.title_text
                         },
-                    ),
-                    style: Style {
-                        width: Val::Px(50.),
-                        ..default()
                     },
+                ),
+                style: Style {
+                    width: Val::Px(50.),
                     ..default()
-                })
-                .id();
+                },
+                ..default()
+            })
+            .id();
 
-            let name_text = commands
-                .spawn(TextBundle {
-                    text: Text::from_section(
-                        display_name,
-                        TextStyle {
-                            font: assets.font.clone(),
-                            font_size: 30.,
-                            color: if is_us { OUR_SCORE_TEXT } else { TITLE_TEXT },
+        let name_text = commands
+            .spawn(TextBundle {
+                text: Text::from_section(
+                    name,
+                    TextStyle {
+                        // Names from the online leaderboard can be in any script
+                        font: localization.font_for(name, &assets),
+                        font_size: 30.,
+                        color: if is_us {
+                            palette.our_score_text
+                        } else {
+                            palette.title_text
                         },
-                    ),
-                    style: Style {
-                        width: Val::Px(300.),
-                        overflow: Overflow::clip(),
-                        ..default()
                     },
+                ),
+                style: Style {
+                    width: Val::Px(300.),
+                    overflow: Overflow::clip(),
                     ..default()
-                })
-                .id();
+                },
+                ..default()
+            })
+            .id();
 
-            let score_text = commands
-                .spawn(TextBundle {
-                    text: Text::from_section(
-                        format!("{:.3}", display_score),
-                        TextStyle {
-                            font: assets.font.clone(),
-                            font_size: 30.,
-                            color: if is_us { OUR_SCORE_TEXT } else { TITLE_TEXT },
+        let score_text = commands
+            .spawn(TextBundle {
+                text: Text::from_section(
+                    format!("{:.3}", score),
+                    TextStyle {
+                        font: localization.font(&assets),
+                        font_size: 30.,
+                        color: if is_us {
+                            palette.our_score_text
+                        } else {
+                            palette.title_text
                         },
-                    ),
-                    ..default()
-                })
-                .id();
+                    },
+                ),
+                ..default()
+            })
+            .id();
 
-            commands
-                .entity(row)
-                .push_children(&[rank_text, name_text, score_text]);
+        commands
+            .entity(row)
+            .push_children(&[rank_text, name_text, score_text]);
 
-            commands.entity(container).add_child(row);
-        }
+        commands.entity(container).add_child(row);
     }
 }
 
-fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
+fn spawn_leaderboard(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    last_run: Res<LastRun>,
+    history: Res<RunHistorySetting>,
+    run_stats: Res<RunStats>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
     info!("spawn_leaderboard");
 
+    let font = localization.font(&assets);
+
     let title_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 60.0,
-        color: TITLE_TEXT,
+        color: palette.title_text,
     };
     let button_style = Style {
         width: Val::Px(250.0),
@@ -252,9 +821,9 @@ fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
         ..default()
     };
     let button_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 30.0,
-        color: BUTTON_TEXT,
+        color: palette.button_text,
     };
 
     let root = commands
@@ -283,31 +852,32 @@ fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
                 padding: UiRect::all(Val::Px(20.)),
                 ..default()
             },
-            background_color: CONTAINER_BACKGROUND.into(),
+            background_color: palette.container_background.into(),
             ..default()
         })
         .id();
 
     let title = commands
         .spawn(
-            TextBundle::from_section("Leaderboard", title_text_style).with_style(Style {
-                margin: UiRect {
-                    bottom: Val::Px(10.0),
+            TextBundle::from_section(localization.get("leaderboard.title"), title_text_style)
+                .with_style(Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
                     ..default()
-                },
-                ..default()
-            }),
+                }),
         )
         .id();
 
     let loading = commands
         .spawn((
             TextBundle::from_section(
-                "Loading...",
+                localization.get("leaderboard.loading"),
                 TextStyle {
-                    font: assets.font.clone(),
+                    font: font.clone(),
                     font_size: 30.0,
-                    color: TITLE_TEXT,
+                    color: palette.title_text,
                 },
             )
             .with_style(Style {
@@ -321,6 +891,19 @@ fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
         ))
         .id();
 
+    let error_buttons = commands
+        .spawn((NodeBundle::default(), ErrorButtonsContainer))
+        .id();
+
+    let summary = spawn_run_summary(
+        &mut commands,
+        &font,
+        &localization,
+        &last_run,
+        &history,
+        &palette,
+    );
+
     let scores_container = commands
         .spawn((
             NodeBundle {
@@ -338,11 +921,76 @@ fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
         ))
         .id();
 
+    let page_text = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: palette.title_text,
+                },
+            ),
+            PageText,
+        ))
+        .id();
+
+    let queue_text = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: palette.title_text,
+                },
+            ),
+            QueueText,
+        ))
+        .id();
+
+    let page_buttons = commands
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (label, button) in [
+                ("<", LeaderboardButton::PreviousPage),
+                (
+                    localization.get("leaderboard.me"),
+                    LeaderboardButton::AroundMe,
+                ),
+                (">", LeaderboardButton::NextPage),
+            ] {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(75.0),
+                                ..button_style.clone()
+                            },
+                            background_color: palette.normal_button.into(),
+                            ..default()
+                        },
+                        Focusable::default(),
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, button_text_style.clone()));
+                    });
+            }
+        })
+        .id();
+
     let play_again = commands
         .spawn((
             ButtonBundle {
                 style: button_style,
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
@@ -351,54 +999,164 @@ fn spawn_leaderboard(mut commands: Commands, assets: Res<GameAssets>) {
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                "Play Again",
+                localization.get("common.play_again"),
                 button_text_style.clone(),
             ));
         })
         .id();
 
-    commands.entity(root).push_children(&[container]);
+    let stats = spawn_run_stats(&mut commands, &font, &localization, &run_stats, &palette);
+
+    commands.entity(root).push_children(&[container, stats]);
 
-    commands
-        .entity(container)
-        .push_children(&[title, loading, scores_container, play_again]);
+    commands.entity(container).push_children(&[
+        title,
+        summary,
+        loading,
+        error_buttons,
+        scores_container,
+        page_text,
+        page_buttons,
+        queue_text,
+        play_again,
+    ]);
 }
 
-fn create_player(
-    mut leaderboard: ResMut<Leaderboard>,
-    leaderboard_setting: Res<LeaderboardSetting>,
+fn create_player<B: LeaderboardBackend>(
+    mut backend: ResMut<B>,
+    mut player_name: ResMut<PlayerNameSetting>,
 ) {
-    if let Some(player) = &**leaderboard_setting {
-        info!("as_playering with {:?}", player);
+    let name = match &**player_name {
+        Some(name) => name.clone(),
+        None => {
+            let name = random_name();
+            info!("picked a name for a new player: {:?}", name);
+            player_name.0 = Some(name.clone());
+            name
+        }
+    };
 
-        leaderboard.as_player(player.clone());
-    } else {
-        info!("creating new player");
-        leaderboard.create_player(Some(&random_name()));
+    backend.login(&name);
+}
+
+fn queue_score(
+    race_time: Res<RaceTime>,
+    recorder: Res<ReplayRecorder>,
+    mut pending_scores: ResMut<PendingScoresSetting>,
+    mut queue: ResMut<ScoreQueue>,
+) {
+    let score = race_time.elapsed_secs();
+    let replay = Replay::from_recording(&recorder, score);
+
+    pending_scores.push(PendingScore {
+        score,
+        replay: replay.encode(),
+    });
+
+    // Each score carries a replay, so a long offline session could outgrow the save
+    // file. Only the best scores can matter on the leaderboard, so drop the slowest,
+    // oldest first, but never one that's been sent and might still make it.
+    while pending_scores.len() > MAX_PENDING_SCORES {
+        let skip = usize::from(!queue.attempts.is_empty());
+        let Some((slowest, _)) = pending_scores
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .rev()
+            .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+        else {
+            break;
+        };
+        pending_scores.remove(slowest);
+    }
+
+    // Don't make the player wait on an old backoff to see their new score.
+    if queue.in_flight.is_none() {
+        queue.timer = Timer::from_seconds(0., TimerMode::Once);
     }
 }
 
-fn save_score(race_time: Res<RaceTime>, leaderboard: Res<Leaderboard>) {
-    info!("sending score. player is: {:?}", leaderboard.get_player());
-    leaderboard.send_score(race_time.elapsed_secs());
+fn queue_text(
+    pending_scores: Res<PendingScoresSetting>,
+    queue: Res<ScoreQueue>,
+    status: Res<ConnectionStatus>,
+    mut query: Query<&mut Text, With<QueueText>>,
+    localization: Res<Localization>,
+) {
+    let value = match (pending_scores.len(), queue.in_flight) {
+        (0, _) => String::new(),
+        (_, Some(_)) => localization.get("leaderboard.sending").to_string(),
+        (n, None) => {
+            let queued = localization.format(
+                "leaderboard.queued",
+                &[&n, &queue.timer.remaining_secs().ceil()],
+            );
+            match status.send_error {
+                Some(error) => format!("{}\n{}", localization.get(error), queued),
+                None => queued,
+            }
+        }
+    };
+
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
 }
 
 fn button_actions(
     buttons: Query<&LeaderboardButton>,
     mut events: EventReader<NavEvent>,
     mut next_state: ResMut<NextState<GameState>>,
+    leaderboard_scores: Res<LeaderboardScores>,
+    mut page_start: ResMut<PageStart>,
+    mut retry_events: EventWriter<RetryLeaderboard>,
+    mut refreshing: ResMut<Refreshing>,
+    mut refresh_timer: ResMut<RefreshTimer>,
+    mut error: ResMut<LeaderboardError>,
+    mut status: ResMut<ConnectionStatus>,
 ) {
+    let last_page_start = leaderboard_scores.scores.len().saturating_sub(PAGE_SIZE);
+
     for button in events.nav_iter().activated_in_query(&buttons) {
         match button {
             LeaderboardButton::PlayAgain => {
                 next_state.set(GameState::MainMenu);
             }
+            LeaderboardButton::Retry => {
+                retry_events.send(RetryLeaderboard);
+                // `retry` will sign in again
+                if status.sign_in == SignIn::Failed {
+                    status.sign_in = SignIn::Pending;
+                }
+                **refreshing = true;
+                refresh_timer.reset();
+                **error = None;
+            }
+            LeaderboardButton::PreviousPage => {
+                **page_start = page_start.saturating_sub(PAGE_SIZE);
+            }
+            LeaderboardButton::AroundMe => {
+                **page_start = leaderboard_scores.around_us();
+            }
+            LeaderboardButton::NextPage => {
+                **page_start = (**page_start + PAGE_SIZE).min(last_page_start);
+            }
         }
     }
 }
 
-fn cleanup(mut commands: Commands, query: Query<Entity, With<LeaderboardMarker>>) {
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<LeaderboardMarker>>,
+    mut leaderboard_scores: ResMut<LeaderboardScores>,
+    mut page_start: ResMut<PageStart>,
+) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
+
+    *leaderboard_scores = LeaderboardScores::default();
+    **page_start = 0;
 }
@@ -0,0 +1,242 @@
+//! A minimal leaderboard that can be hosted anywhere, for private leaderboards and
+//! for testing against `src/bin/leaderboard_server.rs`.
+//!
+//! The protocol is plain text over HTTP:
+//!
+//! - `GET /scores/<track>` responds with one `<score>\t<player>` line per score.
+//! - `POST /scores/<track>` with a `<score>\t<player>\t<replay>` body adds a score.
+//!
+//! There are no player accounts and no TLS, so this isn't meant for the public internet.
+
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::loading::TRACK_NAME;
+
+use super::{add_backend_systems, LeaderboardBackend, LeaderboardEvent, Score};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The server checks a score's replay before answering, which can take a while.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(90);
+
+pub struct HttpBackendPlugin {
+    pub url: String,
+}
+impl Plugin for HttpBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HttpLeaderboard::new(&self.url))
+            .add_systems(Update, poll_responses);
+
+        add_backend_systems::<HttpLeaderboard>(app);
+    }
+}
+
+enum Response {
+    ScoreSent(u32, Result<(), RequestError>),
+    Scores(Result<Vec<Score>, RequestError>),
+}
+
+enum RequestError {
+    /// The server answered with a 4xx status, so the same request would fail again.
+    Rejected(String),
+    /// The server couldn't be reached, or had a problem of its own.
+    Failed(String),
+}
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(e) | Self::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct HttpLeaderboard {
+    host: String,
+    path: String,
+    player: Option<String>,
+    logged_in: bool,
+    scores: Vec<Score>,
+    responses: Arc<Mutex<Vec<Response>>>,
+}
+
+impl HttpLeaderboard {
+    fn new(url: &str) -> Self {
+        let url = url.trim_start_matches("http://").trim_end_matches('/');
+        let (host, path) = match url.split_once('/') {
+            Some((host, path)) => (host.to_string(), format!("/{}", path)),
+            None => (url.to_string(), String::new()),
+        };
+
+        Self {
+            host,
+            path,
+            player: None,
+            logged_in: false,
+            scores: vec![],
+            responses: default(),
+        }
+    }
+
+    /// Makes a request on another thread, and queues up the response for `poll_responses`.
+    fn request(
+        &self,
+        method: &'static str,
+        body: String,
+        on_response: impl FnOnce(Result<String, RequestError>) -> Response + Send + 'static,
+    ) {
+        let host = self.host.clone();
+        let path = format!("{}/scores/{}", self.path, TRACK_NAME);
+        let responses = self.responses.clone();
+
+        std::thread::spawn(move || {
+            let response = on_response(http_request(&host, method, &path, &body));
+            responses.lock().unwrap().push(response);
+        });
+    }
+}
+
+impl LeaderboardBackend for HttpLeaderboard {
+    fn login(&mut self, name: &str) {
+        self.player = Some(name.to_string());
+        self.logged_in = true;
+    }
+
+    fn player_name(&self) -> Option<String> {
+        self.player.clone()
+    }
+
+    fn submit_score(&mut self, send: u32, score: f32, replay: &str) {
+        let Some(player) = &self.player else {
+            return;
+        };
+
+        self.request(
+            "POST",
+            format!("{}\t{}\t{}", score, player, replay),
+            move |result| Response::ScoreSent(send, result.map(|_| ())),
+        );
+    }
+
+    fn fetch_scores(&mut self) {
+        self.request("GET", String::new(), |result| {
+            Response::Scores(result.map(|body| {
+                body.lines()
+                    .filter_map(|line| {
+                        let (score, player) = line.split_once('\t')?;
+                        Some(Score {
+                            player: player.to_string(),
+                            score: score.parse().ok()?,
+                        })
+                    })
+                    .collect()
+            }))
+        });
+    }
+
+    fn scores(&self) -> Vec<Score> {
+        self.scores.clone()
+    }
+}
+
+fn poll_responses(mut backend: ResMut<HttpLeaderboard>, mut events: EventWriter<LeaderboardEvent>) {
+    if backend.logged_in {
+        backend.logged_in = false;
+        events.send(LeaderboardEvent::CreatePlayerSuccess);
+    }
+
+    let responses = std::mem::take(&mut *backend.responses.lock().unwrap());
+
+    for response in responses {
+        match response {
+            Response::ScoreSent(send, Ok(())) => {
+                events.send(LeaderboardEvent::SendScoreSuccess(send));
+            }
+            Response::ScoreSent(send, Err(RequestError::Rejected(e))) => {
+                warn!("score was rejected: {}", e);
+                events.send(LeaderboardEvent::SendScoreRejected(send));
+            }
+            Response::ScoreSent(send, Err(e)) => {
+                warn!("failed to send score: {}", e);
+                events.send(LeaderboardEvent::SendScoreFailure(send));
+            }
+            Response::Scores(Ok(scores)) => {
+                backend.scores = scores;
+                events.send(LeaderboardEvent::RefreshSuccess);
+            }
+            Response::Scores(Err(e)) => {
+                warn!("failed to refresh leaderboard: {}", e);
+                events.send(LeaderboardEvent::RefreshFailure);
+            }
+        }
+    }
+}
+
+/// Makes a blocking HTTP/1.0 request, returning the body of a successful response.
+fn http_request(host: &str, method: &str, path: &str, body: &str) -> Result<String, RequestError> {
+    let failed = |e: std::io::Error| RequestError::Failed(e.to_string());
+
+    // Like a browser, assume port 80 unless the url has one
+    let addr = if host.contains(':') && !host.ends_with(']') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(failed)?
+        .next()
+        .ok_or_else(|| RequestError::Failed(format!("could not resolve {}", host)))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(failed)?;
+    stream
+        .set_read_timeout(Some(RESPONSE_TIMEOUT))
+        .map_err(failed)?;
+    stream
+        .set_write_timeout(Some(CONNECT_TIMEOUT))
+        .map_err(failed)?;
+
+    write!(
+        stream,
+        "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        host,
+        body.len(),
+        body
+    )
+    .map_err(failed)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(failed)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| RequestError::Failed("malformed response".to_string()))?;
+
+    let status = head.lines().next().unwrap_or_default();
+    match status.split(' ').nth(1) {
+        Some(code) if code.starts_with('2') => {}
+        Some(code) if code.starts_with('4') => {
+            return Err(RequestError::Rejected(format!(
+                "{} {}",
+                status,
+                body.trim()
+            )));
+        }
+        _ => {
+            return Err(RequestError::Failed(format!(
+                "unexpected response: {}",
+                status
+            )))
+        }
+    }
+
+    Ok(body.to_string())
+}
@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_jornet::{JornetEvent, JornetPlugin, Leaderboard};
+
+use crate::{
+    settings::{LeaderboardSetting, PlayerNameSetting},
+    GameState,
+};
+
+use super::{add_backend_systems, create_player, LeaderboardBackend, LeaderboardEvent, Score};
+
+pub struct JornetBackendPlugin {
+    pub id: &'static str,
+    pub key: &'static str,
+}
+impl Plugin for JornetBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(JornetPlugin::with_leaderboard(self.id, self.key))
+            .add_systems(Update, (save_leaderboard_setting, forward_events))
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                restore_player.before(create_player::<Leaderboard>),
+            );
+
+        add_backend_systems::<Leaderboard>(app);
+    }
+}
+
+impl LeaderboardBackend for Leaderboard {
+    fn login(&mut self, name: &str) {
+        // Jornet has no way to rename a player, so when the name was changed
+        // in the name entry screen, we register a new player with that name. The
+        // name entry screen asks before a rename leaves the old player behind.
+        if self.get_player().is_some_and(|player| player.name == name) {
+            return;
+        }
+
+        info!("creating new player with name {:?}", name);
+        self.create_player(Some(name));
+    }
+
+    fn player_name(&self) -> Option<String> {
+        self.get_player().map(|player| player.name.clone())
+    }
+
+    // Nothing on jornet's side checks the replay, so it would only make every score
+    // bigger. `forward_events` works out which send a reply is for.
+    fn submit_score(&mut self, _send: u32, score: f32, _replay: &str) {
+        self.send_score(score);
+    }
+
+    fn fetch_scores(&mut self) {
+        self.refresh_leaderboard();
+    }
+
+    fn scores(&self) -> Vec<Score> {
+        self.get_leaderboard()
+            .into_iter()
+            .map(|score| Score {
+                player: score.player,
+                score: score.score,
+            })
+            .collect()
+    }
+}
+
+/// Picks up where we left off with the player saved in `LeaderboardSetting`.
+fn restore_player(
+    mut leaderboard: ResMut<Leaderboard>,
+    leaderboard_setting: Res<LeaderboardSetting>,
+    mut player_name: ResMut<PlayerNameSetting>,
+) {
+    let Some(player) = &**leaderboard_setting else {
+        return;
+    };
+
+    if player_name.is_none() {
+        player_name.0 = Some(player.name.clone());
+    }
+
+    info!("as_playering with {:?}", player);
+
+    leaderboard.as_player(player.clone());
+}
+
+fn save_leaderboard_setting(
+    mut leaderboard_setting: ResMut<LeaderboardSetting>,
+    mut events: EventReader<JornetEvent>,
+    leaderboard: Res<Leaderboard>,
+) {
+    if !events
+        .read()
+        .any(|e| matches!(*e, JornetEvent::CreatePlayerSuccess))
+    {
+        return;
+    }
+
+    if let Some(player) = leaderboard.get_player() {
+        leaderboard_setting.0 = Some(player.clone());
+    }
+}
+
+/// Jornet doesn't say which send a reply is for, but answers every one. Sends are
+/// numbered from 0 in the order they're made, so replies are numbered the same way.
+fn forward_events(
+    mut jornet_events: EventReader<JornetEvent>,
+    mut events: EventWriter<LeaderboardEvent>,
+    mut replies: Local<u32>,
+) {
+    for event in jornet_events.read() {
+        let mut reply = || {
+            let send = *replies;
+            *replies = send.wrapping_add(1);
+            send
+        };
+
+        events.send(match event {
+            JornetEvent::CreatePlayerSuccess => LeaderboardEvent::CreatePlayerSuccess,
+            JornetEvent::CreatePlayerFailure => LeaderboardEvent::CreatePlayerFailure,
+            JornetEvent::SendScoreSuccess => LeaderboardEvent::SendScoreSuccess(reply()),
+            JornetEvent::SendScoreFailure => LeaderboardEvent::SendScoreFailure(reply()),
+            JornetEvent::RefreshLeaderboardSuccess => LeaderboardEvent::RefreshSuccess,
+            JornetEvent::RefreshLeaderboardFailure => LeaderboardEvent::RefreshFailure,
+        });
+    }
+}
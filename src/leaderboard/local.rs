@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+
+use crate::{
+    loading::TRACK_NAME,
+    settings::{LocalLeaderboardSetting, LocalScore},
+};
+
+use super::{add_backend_systems, LeaderboardBackend, LeaderboardEvent, Score};
+
+/// The number of scores we keep around for each track.
+const MAX_LOCAL_SCORES: usize = 100;
+
+/// Keeps scores in the save file, for builds without leaderboard credentials.
+pub struct LocalBackendPlugin;
+impl Plugin for LocalBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LocalLeaderboard>()
+            .add_systems(Update, process_requests);
+
+        add_backend_systems::<LocalLeaderboard>(app);
+    }
+}
+
+/// Requests are queued up here and handled by `process_requests`, which has access
+/// to the scores in `LocalLeaderboardSetting`.
+#[derive(Resource, Default)]
+pub struct LocalLeaderboard {
+    player: Option<String>,
+    logging_in: bool,
+    submitted: Vec<(u32, f32)>,
+    refresh_requested: bool,
+    scores: Vec<Score>,
+}
+
+impl LeaderboardBackend for LocalLeaderboard {
+    fn login(&mut self, name: &str) {
+        self.player = Some(name.to_string());
+        self.logging_in = true;
+    }
+
+    fn player_name(&self) -> Option<String> {
+        self.player.clone()
+    }
+
+    // There's nobody to cheat here but ourselves, so the replay isn't kept.
+    fn submit_score(&mut self, send: u32, score: f32, _replay: &str) {
+        self.submitted.push((send, score));
+    }
+
+    fn fetch_scores(&mut self) {
+        self.refresh_requested = true;
+    }
+
+    fn scores(&self) -> Vec<Score> {
+        self.scores.clone()
+    }
+}
+
+fn process_requests(
+    mut backend: ResMut<LocalLeaderboard>,
+    mut local_leaderboard: ResMut<LocalLeaderboardSetting>,
+    mut events: EventWriter<LeaderboardEvent>,
+) {
+    if backend.logging_in {
+        backend.logging_in = false;
+        events.send(LeaderboardEvent::CreatePlayerSuccess);
+    }
+
+    if !backend.submitted.is_empty() {
+        let Some(name) = backend.player.clone() else {
+            for (send, _) in backend.submitted.drain(..) {
+                events.send(LeaderboardEvent::SendScoreFailure(send));
+            }
+            return;
+        };
+
+        let scores = local_leaderboard.entry(TRACK_NAME.to_string()).or_default();
+        for (send, score) in backend.submitted.drain(..) {
+            info!("saving local score. player is: {:?}", name);
+
+            scores.push(LocalScore {
+                player: name.clone(),
+                score,
+            });
+            events.send(LeaderboardEvent::SendScoreSuccess(send));
+        }
+        scores
+            .sort_unstable_by(|s1, s2| s1.score.partial_cmp(&s2.score).unwrap_or(Ordering::Equal));
+        scores.truncate(MAX_LOCAL_SCORES);
+    }
+
+    if backend.refresh_requested {
+        backend.refresh_requested = false;
+
+        backend.scores = local_leaderboard
+            .get(TRACK_NAME)
+            .map(|scores| {
+                scores
+                    .iter()
+                    .map(|score| Score {
+                        player: score.player.clone(),
+                        score: score.score,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        events.send(LeaderboardEvent::RefreshSuccess);
+    }
+}
@@ -0,0 +1,1251 @@
+mod achievements;
+mod camera;
+mod countdown;
+mod engine_sound;
+mod freeze;
+mod game_over;
+mod graphics;
+mod history;
+mod leaderboard;
+mod loading;
+mod localization;
+mod main_menu;
+mod minimap;
+mod mixer;
+mod music;
+mod name_entry;
+mod particles;
+mod pause_menu;
+mod photo_mode;
+mod random_name;
+mod replay;
+mod save;
+mod settings;
+mod settings_menu;
+mod splits;
+mod stats;
+mod ui;
+
+use std::{f32::consts::TAU, time::Duration};
+#[cfg(feature = "debugdump")]
+use std::{fs::File, io::Write};
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    asset::AssetMetaCheck,
+    color::palettes::css::{GRAY, GREEN, ORANGE},
+    core_pipeline::tonemapping::Tonemapping,
+    log::LogPlugin,
+    pbr::CascadeShadowConfigBuilder,
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        settings::WgpuSettings,
+        view::RenderLayers,
+        RenderPlugin,
+    },
+    time::Stopwatch,
+    transform::TransformSystem,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+
+use bevy_alt_ui_navigation_lite::{systems::InputMapping, DefaultNavigationPlugins};
+use bevy_rapier3d::{parry::shape::SharedShape, prelude::*};
+use bevy_tiling_background::{
+    BackgroundImageBundle, BackgroundMaterial, SetImageRepeatingExt, TilingBackgroundPlugin,
+};
+
+use achievements::AchievementsPlugin;
+use camera::CameraPlugin;
+use countdown::CountdownPlugin;
+use engine_sound::EngineSoundPlugin;
+use game_over::GameOverPlugin;
+use graphics::GraphicsPlugin;
+use history::HistoryPlugin;
+use interpolation::Ease;
+use leaderboard::LeaderboardPlugin;
+use leafwing_input_manager::{axislike::AxisType, prelude::*};
+use loading::{AudioAssets, GameAssets, LoadingPlugin, TRACK_NAME};
+use localization::{Localization, LocalizationPlugin};
+use main_menu::MainMenuPlugin;
+use minimap::{MinimapIcon, MinimapPlugin};
+use mixer::{AudioBus, Mixer, MixerPlugin};
+use music::MusicPlugin;
+use name_entry::NameEntryPlugin;
+use particles::ParticlesPlugin;
+use pause_menu::{pause_menu_inactive, PauseMenuPlugin};
+use photo_mode::{photo_mode_inactive, PhotoModePlugin};
+use replay::{ReplayPlugin, VerifyReplayPlugin};
+use save::{DefaultSettingsPlugin, SavePlugin};
+use settings::{CameraSetting, ReducedMotionSetting};
+use settings_menu::SettingsMenuPlugin;
+use splits::{Checkpoint, SplitsPlugin};
+use stats::{RunStats, StatsPlugin};
+use ui::{Palette, TrickText, UiPlugin};
+
+pub use replay::Replay;
+
+const ROT_SPEED: f32 = 8.;
+const JUMP_IMPULSE: f32 = 175.;
+const DRIVE_FORCE: f32 = 400.;
+const BASE_SPEED_LIMIT: f32 = 20.;
+const BOOST_SPEED_LIMIT: f32 = 30.;
+const BASE_BOOST_TIMER: f32 = 2.;
+/// Bump this when changing anything that affects how a race plays out, so that
+/// replays recorded with the old handling are no longer accepted.
+const TUNING_VERSION: u32 = 1;
+
+#[derive(Component, Default, Deref, DerefMut)]
+struct WheelsOnGround(u8);
+#[derive(Component, Default, Deref, DerefMut)]
+struct JumpWheelsOnGround(u8);
+
+#[derive(Component, Debug, Default, Deref, DerefMut)]
+struct BonkStatus(bool);
+
+#[derive(Component)]
+struct Player;
+/// The player's input for this frame. This comes from `ActionState<Action>` while
+/// playing, or from a replay that is being verified.
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq)]
+struct PlayerInput {
+    back: bool,
+    forward: bool,
+    rotate_left: bool,
+    rotate_right: bool,
+    jump: bool,
+}
+impl PlayerInput {
+    fn to_bits(self) -> u8 {
+        self.back as u8
+            | (self.forward as u8) << 1
+            | (self.rotate_left as u8) << 2
+            | (self.rotate_right as u8) << 3
+            | (self.jump as u8) << 4
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            back: bits & 1 != 0,
+            forward: bits & 1 << 1 != 0,
+            rotate_left: bits & 1 << 2 != 0,
+            rotate_right: bits & 1 << 3 != 0,
+            jump: bits & 1 << 4 != 0,
+        }
+    }
+}
+#[derive(Component)]
+struct Wheel;
+/// A special wheel, slightly larger than the normal wheel. When at
+/// least one `JumpWheel` is touching the track, the player is allowed
+/// to jump.
+///
+/// This works around some frustrating jank where the player might be
+/// "mid-air" for 5 frames at a time while traveling on flat ground.
+#[derive(Component)]
+struct JumpWheel;
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash, States, Default)]
+enum GameState {
+    #[default]
+    Loading,
+    Decorating,
+    Pipelines,
+    MainMenu,
+    NameEntry,
+    Achievements,
+    Playing,
+    Leaderboard,
+    GameOver,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct AfterPhysics;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum GameSet {
+    Input,
+    Movement,
+}
+
+/// Present when running without a window or a renderer, to verify a replay.
+#[derive(Resource)]
+struct Headless;
+
+#[derive(Component, Deref, DerefMut)]
+struct SpeedLimit(f32);
+
+#[derive(Component, Default)]
+struct TrickStatus {
+    rotation: f32,
+    front_flips: u32,
+    back_flips: u32,
+    start_x: f32,
+    hang_time: f32,
+}
+impl TrickStatus {
+    fn reset(&mut self) {
+        self.rotation = 0.;
+        self.front_flips = 0;
+        self.back_flips = 0;
+        self.hang_time = 0.;
+    }
+}
+#[derive(Component, Default, Deref, DerefMut)]
+struct LastTrick(Trick);
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+pub struct Trick {
+    front_flips: u32,
+    back_flips: u32,
+    fakie: bool,
+}
+
+#[derive(Component, Default)]
+struct Boost {
+    remaining: f32,
+}
+
+#[derive(Component)]
+struct Track;
+#[derive(Component)]
+struct FinishLine;
+#[derive(Component)]
+struct PlaceholderCombine;
+#[derive(Resource, Deref, DerefMut)]
+struct RaceTime(Stopwatch);
+impl Default for RaceTime {
+    fn default() -> Self {
+        let mut watch = Stopwatch::default();
+        watch.pause();
+
+        Self(watch)
+    }
+}
+#[derive(Resource)]
+struct Zoom {
+    from: f32,
+    target: f32,
+    timer: Timer,
+}
+impl Default for Zoom {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(0.7, TimerMode::Once);
+        timer.pause();
+
+        Self {
+            from: 20.,
+            target: 80.,
+            timer,
+        }
+    }
+}
+impl Zoom {
+    /// The camera's distance from the track, partway through the tween if one is running.
+    fn current(&self) -> f32 {
+        if self.timer.paused() {
+            return self.target;
+        }
+
+        self.from
+            .lerp(self.target, Ease::quadratic_in_out(self.timer.fraction()))
+    }
+}
+#[derive(Component, Deref, DerefMut)]
+struct JumpCooldown(bool);
+impl Default for JumpCooldown {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Event)]
+struct FinishedEvent;
+
+/// Sent when the player lands a trick.
+#[derive(Event)]
+struct TrickEvent(Trick);
+
+#[derive(Component)]
+pub struct MainCamera;
+
+const LAVA: f32 = -200.;
+
+/// Runs the game, or with `verify_replay`, runs that replay without a window and exits
+/// successfully only if it finishes in the time it claims.
+pub fn run(verify_replay: Option<Replay>) -> AppExit {
+    let mut app = App::new();
+
+    let default_plugins = DefaultPlugins
+        .set(LogPlugin {
+            filter: "info,bevy_ecs=debug,wgpu_core=warn,wgpu_hal=warn,combine_racers=debug".into(),
+            level: bevy::log::Level::DEBUG,
+            ..default()
+        })
+        .set(AssetPlugin {
+            // Workaround for Bevy attempting to load .meta files in wasm builds. On itch,
+            // the CDN servers HTTP 403 errors instead of 404 when files don't exists, which
+            // causes Bevy to break.
+            meta_check: AssetMetaCheck::Never,
+            ..default()
+        })
+        .build();
+
+    let default_plugins = if verify_replay.is_some() {
+        // Simulate as fast as we can, without a window or a gpu.
+        app.insert_resource(Headless)
+            .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO));
+
+        default_plugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .disable::<WinitPlugin>()
+    } else {
+        default_plugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Combine Racers".into(),
+                ..default()
+            }),
+            ..default()
+        })
+    };
+
+    #[cfg(feature = "debugdump")]
+    let default_plugins = default_plugins.disable::<bevy::log::LogPlugin>();
+
+    app.add_plugins(default_plugins);
+
+    app.insert_resource(ClearColor(Color::BLACK))
+        .init_state::<GameState>()
+        .add_plugins(LoadingPlugin)
+        .add_plugins(LocalizationPlugin)
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(InputMapping {
+            keyboard_navigation: true,
+            ..default()
+        })
+        .add_plugins(TilingBackgroundPlugin::<BackgroundMaterial>::default())
+        .add_plugins(InputManagerPlugin::<Action>::default())
+        .add_plugins(DefaultNavigationPlugins)
+        .add_plugins(UiPlugin)
+        .add_plugins(MainMenuPlugin)
+        .add_plugins(NameEntryPlugin)
+        .add_plugins(CountdownPlugin)
+        .add_plugins(GameOverPlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(SplitsPlugin)
+        .add_plugins(AchievementsPlugin)
+        .add_plugins(GraphicsPlugin)
+        .add_plugins(SettingsMenuPlugin)
+        .add_plugins(PauseMenuPlugin)
+        .add_plugins(PhotoModePlugin)
+        .add_plugins(CameraPlugin)
+        .add_plugins(MinimapPlugin)
+        .add_plugins(ParticlesPlugin)
+        .add_plugins(EngineSoundPlugin)
+        .add_plugins(MusicPlugin)
+        .add_plugins(MixerPlugin);
+
+    // When verifying a replay, stay away from the save file and the leaderboard.
+    match verify_replay {
+        Some(replay) => app
+            .add_plugins(DefaultSettingsPlugin)
+            .add_plugins(VerifyReplayPlugin { replay }),
+        None => app.add_plugins(LeaderboardPlugin).add_plugins(SavePlugin),
+    };
+
+    #[cfg(feature = "inspector")]
+    {
+        app.add_plugins(WorldInspectorPlugin::new());
+        app.add_plugins(RapierDebugRenderPlugin::default());
+    }
+
+    app.init_resource::<RaceTime>().init_resource::<Zoom>();
+
+    app.add_event::<FinishedEvent>().add_event::<TrickEvent>();
+
+    // TODO we may need apply_deferred somewhere in here
+    app.configure_sets(
+        PostUpdate,
+        AfterPhysics
+            .after(PhysicsSet::Writeback)
+            .before(TransformSystem::TransformPropagate),
+    );
+
+    app.add_systems(Startup, configure_gizmos);
+
+    app.add_systems(OnExit(GameState::Loading), spawn_camera)
+        .add_systems(OnEnter(GameState::Decorating), setup_game)
+        .add_systems(
+            Update,
+            decorate_track.run_if(in_state(GameState::Decorating)),
+        )
+        .add_systems(OnEnter(GameState::Playing), spawn_player)
+        .add_systems(
+            PostUpdate,
+            (
+                camera_follow
+                    .run_if(resource_equals(CameraSetting::Classic))
+                    .run_if(in_state(GameState::Playing).and_then(photo_mode_inactive)),
+                collision_events.run_if(in_state(GameState::Playing)),
+                player_dampening
+                    .in_set(GameSet::Movement)
+                    .run_if(in_state(GameState::Playing)),
+                track_trick
+                    .after(GameSet::Movement)
+                    .run_if(in_state(GameState::Playing)),
+                zoom.run_if(in_state(GameState::Playing).and_then(photo_mode_inactive)),
+            )
+                .in_set(AfterPhysics),
+        )
+        // Do a limited subset of things in the background while
+        // we're showing the leaderboard or game over screen
+        .add_systems(
+            PostUpdate,
+            (
+                player_dampening
+                    .in_set(GameSet::Movement)
+                    .run_if(in_state(GameState::Leaderboard)),
+                camera_follow
+                    .run_if(resource_equals(CameraSetting::Classic))
+                    .run_if(in_state(GameState::Leaderboard)),
+            )
+                .in_set(AfterPhysics),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                player_dampening
+                    .in_set(GameSet::Movement)
+                    .run_if(in_state(GameState::GameOver)),
+                camera_follow
+                    .run_if(resource_equals(CameraSetting::Classic))
+                    .run_if(in_state(GameState::GameOver)),
+            )
+                .in_set(AfterPhysics),
+        )
+        .add_systems(
+            Update,
+            read_player_input
+                .in_set(GameSet::Input)
+                .run_if(in_state(GameState::Playing).and_then(photo_mode_inactive)),
+        )
+        .add_systems(
+            Update,
+            (
+                player_movement.after(GameSet::Input),
+                boost,
+                race_time,
+                game_finished,
+                start_zoom.run_if(photo_mode_inactive),
+                reset_action.run_if(photo_mode_inactive.and_then(pause_menu_inactive)),
+                bonk_sound,
+                death,
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(OnExit(GameState::Leaderboard), reset)
+        .add_systems(OnExit(GameState::GameOver), reset);
+
+    #[cfg(feature = "debugdump")]
+    {
+        let settings = bevy_mod_debugdump::schedule_graph::Settings {
+            ambiguity_enable: false,
+            ambiguity_enable_on_world: false,
+            ..Default::default()
+        };
+
+        let dot = bevy_mod_debugdump::schedule_graph_dot(&mut app, Update, &settings);
+        let mut f = File::create("debugdump_update.dot").unwrap();
+        f.write_all(dot.as_bytes()).unwrap();
+    }
+
+    app.run()
+}
+
+// This is the list of "things in the game I want to be able to do based on input"
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
+enum Action {
+    Back,
+    Forward,
+    LeftRight,
+    RotateLeft,
+    RotateRight,
+    Jump,
+    ToggleZoom,
+    Reset,
+    PhotoMode,
+    Pause,
+}
+
+fn spawn_camera(mut commands: Commands, zoom: Res<Zoom>) {
+    // For the background
+    commands.spawn(Camera2dBundle {
+        camera: Camera {
+            order: -1,
+            ..default()
+        },
+        ..default()
+    });
+
+    // TODO don't render UI to the background camera
+
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., zoom.target),
+            tonemapping: Tonemapping::ReinhardLuminance,
+            ..Default::default()
+        },
+        RenderLayers::from_layers(&[0, 1]),
+        MainCamera,
+    ));
+}
+
+fn decorate_track(
+    mut commands: Commands,
+    mesh_query: Query<(Entity, &Name, &Handle<Mesh>), Without<Collider>>,
+    meshes: Res<Assets<Mesh>>,
+    mut visibility_query: Query<&mut Visibility>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    fn chop_name(name: &str) -> Option<&str> {
+        name.rsplitn(2, '.').last()
+    }
+
+    let mut decorated = false;
+
+    for (mesh_entity, name, mesh_handle) in mesh_query.iter() {
+        match chop_name(name) {
+            Some("Track") => {
+                decorated = true;
+
+                let mut flags = TriMeshFlags::default();
+                flags.set(TriMeshFlags::FIX_INTERNAL_EDGES, true);
+                let (vtx, idx) =
+                    extract_mesh_vertices_indices(meshes.get(mesh_handle).unwrap()).unwrap();
+                let collider: Collider = SharedShape::trimesh_with_flags(vtx, idx, flags).into();
+
+                commands
+                    .entity(mesh_entity)
+                    .insert(ColliderDebugColor(GREEN.into()))
+                    .insert(collider)
+                    .insert(Track);
+
+                info!("Added track collider to {:?}", mesh_entity);
+            }
+            Some("FinishLineCollider") => {
+                decorated = true;
+
+                commands
+                    .entity(mesh_entity)
+                    .insert(ColliderDebugColor(GRAY.into()))
+                    .insert(
+                        Collider::from_bevy_mesh(
+                            meshes.get(mesh_handle).unwrap(),
+                            &ComputedColliderShape::TriMesh,
+                        )
+                        .unwrap(),
+                    )
+                    .insert(Sensor)
+                    .insert(FinishLine);
+
+                if let Ok(mut visibility) = visibility_query.get_mut(mesh_entity) {
+                    *visibility = Visibility::Hidden
+                }
+
+                info!("Added finish line collider to {:?}", mesh_entity);
+            }
+            _ => {}
+        }
+    }
+
+    if decorated {
+        next_state.set(GameState::Pipelines);
+    }
+}
+
+fn setup_game(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut materials: ResMut<Assets<BackgroundMaterial>>,
+) {
+    for layer in loading::background_layers(TRACK_NAME) {
+        let Some(image) = assets.backgrounds.get(layer.path) else {
+            warn!("Background layer {} wasn't loaded", layer.path);
+            continue;
+        };
+
+        commands.set_image_repeating(image.clone());
+
+        commands.spawn(
+            BackgroundImageBundle::from_image(image.clone(), materials.as_mut())
+                .with_movement_scale(layer.movement_scale)
+                .at_z_layer(layer.z_layer),
+        );
+    }
+
+    commands.spawn((
+        Name::new("DirectionalLight"),
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: 6_000.0,
+                shadows_enabled: true,
+                ..default()
+            },
+            cascade_shadow_config: CascadeShadowConfigBuilder {
+                maximum_distance: 100.,
+                ..default()
+            }
+            .into(),
+            transform: Transform::from_rotation(Quat::from_euler(EulerRot::XYZ, -0.4, 0.4, 0.)),
+            ..default()
+        },
+    ));
+
+    // ambient light
+    commands.insert_resource(AmbientLight {
+        brightness: 60.0,
+        ..default()
+    });
+
+    commands.spawn({
+        SceneBundle {
+            scene: assets.track.clone(),
+            ..default()
+        }
+    });
+
+    for (i, checkpoint) in loading::checkpoints(TRACK_NAME).iter().enumerate() {
+        let center = (checkpoint.bottom + checkpoint.top) / 2.;
+
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(checkpoint.x, center, 0.)),
+            Collider::cuboid(1., (checkpoint.top - checkpoint.bottom) / 2., 15.),
+            ColliderDebugColor(GRAY.into()),
+            Sensor,
+            Checkpoint(i as u32),
+        ));
+    }
+
+    // this is super dumb, but spawning the combine causes new render pipelines
+    // to be built which stops the world in web builds and ruins the race start
+    // countdown. so we'll spawn it here instead when it's less disruptive.
+    commands.spawn((
+        SceneBundle {
+            scene: assets.combine.clone(),
+            // the thing has to be "visible" for this to work, so hide it in the track.
+            transform: Transform::from_xyz(0., -4., 0.).with_scale(Vec3::splat(0.001)),
+            ..default()
+        },
+        PlaceholderCombine,
+    ));
+}
+
+fn spawn_player(mut commands: Commands, game_assets: Res<GameAssets>, palette: Res<Palette>) {
+    let mut axes = LockedAxes::empty();
+    axes.insert(LockedAxes::ROTATION_LOCKED_X);
+    axes.insert(LockedAxes::ROTATION_LOCKED_Y);
+    axes.insert(LockedAxes::TRANSLATION_LOCKED_Z);
+
+    let mut input_map = InputMap::new([
+        (Action::Back, KeyCode::ArrowLeft),
+        (Action::Back, KeyCode::KeyA),
+        (Action::Forward, KeyCode::ArrowRight),
+        (Action::Forward, KeyCode::KeyD),
+        (Action::RotateLeft, KeyCode::KeyQ),
+        (Action::RotateRight, KeyCode::KeyE),
+        (Action::Jump, KeyCode::Space),
+        (Action::ToggleZoom, KeyCode::KeyZ),
+        (Action::Reset, KeyCode::Escape),
+        (Action::PhotoMode, KeyCode::KeyP),
+        (Action::Pause, KeyCode::Tab),
+    ]);
+
+    input_map.insert_multiple([
+        (Action::Back, GamepadButtonType::DPadLeft),
+        (Action::Forward, GamepadButtonType::DPadRight),
+        (Action::RotateLeft, GamepadButtonType::LeftTrigger),
+        (Action::RotateRight, GamepadButtonType::RightTrigger),
+        (Action::Jump, GamepadButtonType::South),
+        (Action::ToggleZoom, GamepadButtonType::North),
+        (Action::Reset, GamepadButtonType::Select),
+        (Action::PhotoMode, GamepadButtonType::Start),
+        (Action::Pause, GamepadButtonType::East),
+    ]);
+
+    input_map.insert_multiple([
+        (
+            Action::Back,
+            SingleAxis::negative_only(AxisType::Gamepad(GamepadAxisType::LeftStickX), -0.3),
+        ),
+        (
+            Action::Forward,
+            SingleAxis::positive_only(AxisType::Gamepad(GamepadAxisType::LeftStickX), 0.3),
+        ),
+    ]);
+
+    commands
+        .spawn((
+            Name::new("Player"),
+            SceneBundle {
+                scene: game_assets.combine.clone(),
+                ..default()
+            },
+            WheelsOnGround::default(),
+            JumpWheelsOnGround::default(),
+            JumpCooldown::default(),
+            BonkStatus::default(),
+            SpeedLimit(BASE_SPEED_LIMIT),
+            Boost::default(),
+            TrickStatus::default(),
+            LastTrick::default(),
+            PlayerInput::default(),
+            MinimapIcon {
+                color: palette.boosted_text,
+            },
+            Player,
+        ))
+        .insert((
+            RigidBody::Dynamic,
+            axes,
+            Velocity::default(),
+            Collider::cuboid(1., 1., 1.),
+            ColliderDebugColor(ORANGE.into()),
+            ExternalImpulse::default(),
+            ExternalForce::default(),
+            InputManagerBundle::<Action> {
+                input_map,
+                ..default()
+            },
+            ActiveEvents::COLLISION_EVENTS,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TransformBundle {
+                    local: Transform::from_translation(Vec3::new(-1.5, -0.5, 0.)),
+                    ..default()
+                },
+                ActiveEvents::COLLISION_EVENTS,
+                Collider::ball(1.),
+                ColliderDebugColor(ORANGE.into()),
+                Friction::coefficient(0.1),
+                Restitution::coefficient(0.0),
+                Wheel,
+            ));
+            parent.spawn((
+                TransformBundle {
+                    local: Transform::from_translation(Vec3::new(1.5, -0.5, 0.)),
+                    ..default()
+                },
+                ActiveEvents::COLLISION_EVENTS,
+                Collider::ball(1.),
+                ColliderDebugColor(ORANGE.into()),
+                Friction::coefficient(0.1),
+                Restitution::coefficient(0.0),
+                Wheel,
+            ));
+            parent.spawn((
+                TransformBundle {
+                    local: Transform::from_translation(Vec3::new(-1.5, -0.5, 0.)),
+                    ..default()
+                },
+                ActiveEvents::COLLISION_EVENTS,
+                Collider::ball(1.1),
+                ColliderDebugColor(ORANGE.into()),
+                ColliderMassProperties::Density(0.0),
+                Sensor,
+                JumpWheel,
+            ));
+            parent.spawn((
+                TransformBundle {
+                    local: Transform::from_translation(Vec3::new(1.5, -0.5, 0.)),
+                    ..default()
+                },
+                ActiveEvents::COLLISION_EVENTS,
+                Collider::ball(1.1),
+                ColliderDebugColor(ORANGE.into()),
+                ColliderMassProperties::Density(0.0),
+                Sensor,
+                JumpWheel,
+            ));
+        });
+}
+
+fn read_player_input(mut query: Query<(&ActionState<Action>, &mut PlayerInput), With<Player>>) {
+    for (action_state, mut input) in &mut query {
+        *input = PlayerInput {
+            back: action_state.pressed(&Action::Back),
+            forward: action_state.pressed(&Action::Forward),
+            rotate_left: action_state.pressed(&Action::RotateLeft),
+            rotate_right: action_state.pressed(&Action::RotateRight),
+            jump: action_state.just_pressed(&Action::Jump),
+        };
+    }
+}
+
+fn player_movement(
+    time: Res<Time>,
+    mut query: Query<
+        (
+            &PlayerInput,
+            &mut ExternalForce,
+            &mut ExternalImpulse,
+            &mut Velocity,
+            &JumpWheelsOnGround,
+            &mut JumpCooldown,
+            &Transform,
+        ),
+        With<Player>,
+    >,
+    race_timer: Res<RaceTime>,
+) {
+    if race_timer.paused() {
+        return;
+    }
+
+    for (input, mut force, mut impulse, mut velocity, jump_wheels, mut jump_cooldown, transform) in
+        query.iter_mut()
+    {
+        force.force = Vec3::ZERO;
+
+        if input.back && **jump_wheels >= 1 {
+            force.force = transform.rotation * -Vec3::X * DRIVE_FORCE;
+        }
+        if input.forward && **jump_wheels >= 1 {
+            force.force = transform.rotation * Vec3::X * DRIVE_FORCE;
+        }
+        if input.rotate_left {
+            velocity.angvel += Vec3::Z * ROT_SPEED * time.delta_seconds();
+        }
+        if input.rotate_right {
+            velocity.angvel += -Vec3::Z * ROT_SPEED * time.delta_seconds();
+        }
+        if input.jump && **jump_wheels >= 1 && !**jump_cooldown {
+            // We don't want a jump from an angled ramp to impart any impulse in the backwards
+            // direction, slowing the player down.
+            //
+            // So use only the y component of the current rotation for the jump impulse.
+            // But to enable "wall jumping," use the x component if we're close to vertical.
+            //
+            // An alternative to explore if this turns out to be janky would be to differentiate
+            // between "tracks" and "ramps" and use the old jumping behavior on non-ramps.
+
+            let up = transform.up();
+            let deg = up.angle_between(Vec3::NEG_X).to_degrees();
+
+            if !(20.0..=340.0).contains(&deg) || (160.0..200.0).contains(&deg) {
+                impulse.impulse = Vec3::new(up.x.signum() * JUMP_IMPULSE, 0., 0.);
+            } else {
+                impulse.impulse = Vec3::new(0., up.y.signum() * JUMP_IMPULSE, 0.);
+            }
+
+            **jump_cooldown = true;
+        }
+    }
+}
+
+fn camera_follow(
+    player: Query<&Transform, With<Player>>,
+    mut camera: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+) {
+    for player_transform in player.iter() {
+        for mut camera_transform in camera.iter_mut() {
+            camera_transform.translation.x = player_transform.translation.x;
+            camera_transform.translation.y = player_transform.translation.y;
+        }
+    }
+}
+
+fn collision_events(
+    mut collision_events: EventReader<CollisionEvent>,
+    wheel_query: Query<Entity, With<Wheel>>,
+    jump_wheel_query: Query<Entity, With<JumpWheel>>,
+    track_query: Query<Entity, With<Track>>,
+    finish_line_query: Query<Entity, With<FinishLine>>,
+    body_query: Query<Entity, With<Player>>,
+    mut player_query: Query<
+        (
+            &mut WheelsOnGround,
+            &mut JumpWheelsOnGround,
+            &mut BonkStatus,
+            &mut JumpCooldown,
+        ),
+        With<Player>,
+    >,
+    mut race_time: ResMut<RaceTime>,
+    mut finished_event: EventWriter<FinishedEvent>,
+    mut trick_text: ResMut<TrickText>,
+    mut run_stats: ResMut<RunStats>,
+    localization: Res<Localization>,
+) {
+    for collision_event in collision_events.read() {
+        match collision_event {
+            CollisionEvent::Started(e1, e2, _) => {
+                let finish_line = finish_line_query.iter_many([e1, e2]).count() > 0;
+                let track = track_query.iter_many([e1, e2]).count() > 0;
+                let wheel = wheel_query.iter_many([e1, e2]).count() > 0;
+                let jump_wheel = jump_wheel_query.iter_many([e1, e2]).count() > 0;
+                let body = body_query.iter_many([e1, e2]).count() > 0;
+
+                if jump_wheel && track {
+                    for (_, mut wheels, _, mut jump_cooldown) in player_query.iter_mut() {
+                        wheels.0 += 1;
+
+                        if wheels.0 == 2 {
+                            **jump_cooldown = false;
+                        }
+                    }
+                }
+
+                if wheel && track {
+                    for (mut wheels, _, mut bonk, _) in player_query.iter_mut() {
+                        wheels.0 += 1;
+
+                        if wheels.0 == 2 {
+                            // don't use **bonk, it will trigger change detection
+                            if bonk.0 {
+                                **bonk = false;
+                            }
+                        }
+                    }
+                }
+
+                if (body || wheel) && finish_line {
+                    race_time.pause();
+                    // we have to fire off an event here because you can't
+                    // trigger on_exit and on_enter when changing state from
+                    // a different stage.
+                    finished_event.send(FinishedEvent);
+                }
+
+                if body && track {
+                    for (_, _, mut bonk, _) in player_query.iter_mut() {
+                        // don't use **bonk, it will trigger change detection
+                        if !bonk.0 {
+                            **trick_text = localization.get("trick.bonk").to_string();
+                            **bonk = true;
+                            run_stats.bonks += 1;
+                        }
+                    }
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                let track = track_query.iter_many([e1, e2]).count() > 0;
+                let wheel = wheel_query.iter_many([e1, e2]).count() > 0;
+                let jump_wheel = jump_wheel_query.iter_many([e1, e2]).count() > 0;
+
+                if track && wheel {
+                    for (mut wheels, _, _, _) in player_query.iter_mut() {
+                        wheels.0 -= 1;
+                    }
+                }
+
+                if track && jump_wheel {
+                    for (_, mut wheels, _, _) in player_query.iter_mut() {
+                        wheels.0 -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn game_finished(
+    mut events: EventReader<FinishedEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if events.read().count() > 0 {
+        next_state.set(GameState::Leaderboard);
+    }
+}
+
+fn player_dampening(
+    time: Res<Time>,
+    mut query: Query<(&mut Velocity, &SpeedLimit, &JumpWheelsOnGround), With<Player>>,
+) {
+    for (mut velocity, speed_limit, wheels) in query.iter_mut() {
+        let elapsed = time.delta_seconds();
+        velocity.angvel *= 0.1f32.powf(elapsed);
+
+        // clamp to speed limit
+        if velocity.linvel.length() > **speed_limit && **wheels > 0 {
+            velocity.linvel =
+                (velocity.linvel * 0.1f32.powf(elapsed)).clamp_length_min(**speed_limit);
+        }
+    }
+}
+
+fn track_trick(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<
+        (
+            &mut TrickStatus,
+            &mut LastTrick,
+            &Velocity,
+            &Transform,
+            Ref<WheelsOnGround>,
+            &BonkStatus,
+            &mut Boost,
+        ),
+        With<Player>,
+    >,
+    mut trick_text: ResMut<TrickText>,
+    game_audio: Res<AudioAssets>,
+    mixer: Res<Mixer>,
+    mut run_stats: ResMut<RunStats>,
+    mut trick_events: EventWriter<TrickEvent>,
+    localization: Res<Localization>,
+) {
+    for (mut trick_status, mut last_trick, velocity, transform, wheels, bonk, mut boost) in
+        query.iter_mut()
+    {
+        if **bonk {
+            trick_status.reset();
+        }
+
+        if **wheels == 0 {
+            // if we just left the ground, make a note of our starting
+            // position so we can determine if we went forward or backward
+            if wheels.is_changed() {
+                trick_status.start_x = transform.translation.x;
+                trick_status.hang_time = 0.;
+            }
+
+            let elapsed = time.delta_seconds();
+            let rot = velocity.angvel * elapsed;
+
+            trick_status.rotation += rot.z;
+            trick_status.hang_time += elapsed;
+
+            if trick_status.rotation > TAU {
+                trick_status.back_flips += 1;
+                trick_status.rotation -= TAU;
+            } else if trick_status.rotation < -TAU {
+                trick_status.front_flips += 1;
+                trick_status.rotation += TAU;
+            }
+        } else if wheels.is_changed() {
+            // if a wheel just hit the ground
+
+            run_stats.longest_hang_time = run_stats.longest_hang_time.max(trick_status.hang_time);
+
+            // round up the remainder of the rotation generously, because
+            // the player may have launched from angled ramp. maybe we
+            // should keep track of the launch angle?
+            if trick_status.rotation > 260.0_f32.to_radians() {
+                trick_status.back_flips += 1;
+            } else if trick_status.rotation < -260.0_f32.to_radians() {
+                trick_status.front_flips += 1;
+            }
+
+            let flips = trick_status.front_flips + trick_status.back_flips;
+
+            if flips > 0 {
+                let fakie = transform.translation.x < trick_status.start_x;
+
+                let trick = Trick {
+                    front_flips: trick_status.front_flips,
+                    back_flips: trick_status.back_flips,
+                    fakie,
+                };
+
+                let fresh_bonus = if trick != **last_trick { 1. } else { 0. };
+
+                let boost_duration = BASE_BOOST_TIMER + (flips - 1) as f32 * 1. + fresh_bonus;
+
+                boost.remaining += boost_duration;
+
+                info!("boost +{} ({})", boost_duration, boost.remaining);
+
+                **trick_text = ui::get_trick_text(&trick, &localization);
+
+                **last_trick = trick.clone();
+
+                run_stats.tricks += 1;
+                run_stats.front_flips += trick.front_flips;
+                run_stats.back_flips += trick.back_flips;
+                run_stats.unique_tricks.insert(trick.clone());
+
+                trick_events.send(TrickEvent(trick));
+
+                mixer.play(&mut commands, game_audio.trick.clone(), AudioBus::Sfx);
+            }
+
+            trick_status.reset();
+        }
+    }
+}
+
+fn boost(time: Res<Time>, mut query: Query<(&mut Boost, &mut SpeedLimit), With<Player>>) {
+    for (mut boost, mut speed_limit) in query.iter_mut() {
+        if boost.remaining <= 0. {
+            return;
+        }
+
+        if speed_limit.0 == BASE_SPEED_LIMIT {
+            **speed_limit = BOOST_SPEED_LIMIT;
+            info!("speed limit now {}", **speed_limit);
+        }
+
+        boost.remaining -= time.delta_seconds();
+        if boost.remaining <= 0. {
+            boost.remaining = 0.;
+            **speed_limit = BASE_SPEED_LIMIT;
+            info!("speed limit now {}", **speed_limit);
+        }
+    }
+}
+
+fn race_time(time: Res<Time>, mut race_time: ResMut<RaceTime>) {
+    race_time.tick(time.delta());
+}
+
+fn start_zoom(
+    query: Query<&ActionState<Action>, With<Player>>,
+    reduced_motion: Res<ReducedMotionSetting>,
+    mut zoom: ResMut<Zoom>,
+) {
+    let action_state = query.single();
+    if action_state.just_pressed(&Action::ToggleZoom) && zoom.timer.paused() {
+        (zoom.target, zoom.from) = (zoom.from, zoom.target);
+
+        // Leaving the timer paused jumps straight to the new zoom level
+        if !**reduced_motion {
+            zoom.timer.reset();
+            zoom.timer.unpause();
+        }
+    }
+}
+
+fn zoom(
+    time: Res<Time>,
+    mut zoom: ResMut<Zoom>,
+    mut camera_query: Query<&mut Transform, With<Camera3d>>,
+) {
+    if !zoom.timer.paused() {
+        zoom.timer.tick(time.delta());
+    }
+
+    let mut camera = camera_query.single_mut();
+
+    camera.translation.z = zoom.current();
+
+    if zoom.timer.just_finished() {
+        zoom.timer.pause();
+    }
+}
+
+fn bonk_sound(
+    mut commands: Commands,
+    game_audio: Res<AudioAssets>,
+    mixer: Res<Mixer>,
+    bonk_query: Query<&BonkStatus, (Changed<BonkStatus>, With<Player>)>,
+) {
+    for bonk in &bonk_query {
+        if **bonk {
+            mixer.play(&mut commands, game_audio.bonk.clone(), AudioBus::Sfx);
+        }
+    }
+}
+
+fn death(
+    query: Query<&Transform, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut race_time: ResMut<RaceTime>,
+) {
+    for transform in &query {
+        if transform.translation.y < LAVA {
+            race_time.pause();
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+fn reset_action(
+    query: Query<&ActionState<Action>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut race_time: ResMut<RaceTime>,
+) {
+    let action_state = query.single();
+    if action_state.just_pressed(&Action::Reset) {
+        race_time.pause();
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn reset(
+    mut commands: Commands,
+    player_query: Query<Entity, With<Player>>,
+    mut race_time: ResMut<RaceTime>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    for entity in player_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    race_time.reset();
+    *run_stats = RunStats::default();
+}
+
+fn configure_gizmos(mut config_store: ResMut<GizmoConfigStore>) {
+    for (_, config, _) in config_store.iter_mut() {
+        config.render_layers = RenderLayers::layer(1);
+    }
+}
+
+// See https://github.com/dimforge/bevy_rapier/issues/571
+fn extract_mesh_vertices_indices(
+    mesh: &Mesh,
+) -> Option<(
+    Vec<bevy_rapier3d::na::Point3<bevy_rapier3d::prelude::Real>>,
+    Vec<[u32; 3]>,
+)> {
+    use bevy_rapier3d::math::Real;
+    use bevy_rapier3d::na::Point3;
+
+    let vertices = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?;
+    let indices = mesh.indices()?;
+
+    let vtx: Vec<_> = match vertices {
+        VertexAttributeValues::Float32(vtx) => Some(
+            vtx.chunks(3)
+                .map(|v| Point3::new(v[0] as Real, v[1] as Real, v[2] as Real))
+                .collect(),
+        ),
+        VertexAttributeValues::Float32x3(vtx) => Some(
+            vtx.iter()
+                .map(|v| Point3::new(v[0] as Real, v[1] as Real, v[2] as Real))
+                .collect(),
+        ),
+        _ => None,
+    }?;
+
+    let idx = match indices {
+        Indices::U16(idx) => idx
+            .chunks_exact(3)
+            .map(|i| [i[0] as u32, i[1] as u32, i[2] as u32])
+            .collect(),
+        Indices::U32(idx) => idx.chunks_exact(3).map(|i| [i[0], i[1], i[2]]).collect(),
+    };
+
+    Some((vtx, idx))
+}
@@ -1,24 +1,102 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_asset_loader::prelude::*;
 use bevy_pipelines_ready::{PipelinesReady, PipelinesReadyPlugin};
 
-use crate::GameState;
+use crate::{GameState, Headless};
 
 pub struct LoadingPlugin;
 
 #[derive(Component)]
 pub struct PipelinesMarker;
 
+/// The name of the track in `GameAssets`, used to key per-track records.
+pub const TRACK_NAME: &str = "track_1";
+
+/// A layer of scenery behind the track. Layers that are further away move less as
+/// the camera moves, and are drawn behind layers with a higher `z_layer`.
+pub struct BackgroundLayer {
+    pub path: &'static str,
+    pub movement_scale: f32,
+    pub z_layer: f32,
+}
+
+/// The background layers for each track, from back to front.
+pub const BACKGROUND_LAYERS: &[(&str, &[BackgroundLayer])] = &[(
+    "track_1",
+    &[
+        BackgroundLayer {
+            path: "bg.png",
+            movement_scale: 0.25,
+            z_layer: 0.1,
+        },
+        BackgroundLayer {
+            path: "bg_motes.png",
+            movement_scale: 0.6,
+            z_layer: 0.2,
+        },
+    ],
+)];
+
+pub fn background_layers(track: &str) -> &'static [BackgroundLayer] {
+    BACKGROUND_LAYERS
+        .iter()
+        .find(|(name, _)| *name == track)
+        .map(|(_, layers)| *layers)
+        .unwrap_or_default()
+}
+
+/// A sensor across the track at `x`, reaching from `bottom` to `top`. Tracks can pass
+/// over the same `x` more than once, so the height picks out which pass it's on.
+pub struct CheckpointArea {
+    pub x: f32,
+    pub bottom: f32,
+    pub top: f32,
+}
+
+/// The checkpoints for each track, in the order they're crossed.
+pub const CHECKPOINTS: &[(&str, &[CheckpointArea])] = &[(
+    "track_1",
+    &[
+        CheckpointArea {
+            x: 250.,
+            bottom: -6.,
+            top: 14.,
+        },
+        CheckpointArea {
+            x: 250.,
+            bottom: 48.,
+            top: 75.,
+        },
+        CheckpointArea {
+            x: -150.,
+            bottom: -5.,
+            top: 60.,
+        },
+    ],
+)];
+
+pub fn checkpoints(track: &str) -> &'static [CheckpointArea] {
+    CHECKPOINTS
+        .iter()
+        .find(|(name, _)| *name == track)
+        .map(|(_, checkpoints)| *checkpoints)
+        .unwrap_or_default()
+}
+
 #[derive(AssetCollection, Resource)]
 pub struct GameAssets {
     #[asset(path = "track_1.glb#Scene0")]
     pub track: Handle<Scene>,
     #[asset(path = "combine.glb#Scene0")]
     pub combine: Handle<Scene>,
-    #[asset(path = "bg.png")]
-    pub background: Handle<Image>,
+    /// Every image used by `BACKGROUND_LAYERS`, keyed by path
+    #[asset(paths("bg.png", "bg_motes.png"), collection(typed, mapped))]
+    pub backgrounds: HashMap<String, Handle<Image>>,
     #[asset(path = "NanumPenScript-Tweaked.ttf")]
     pub font: Handle<Font>,
+    /// For text that `font` doesn't have glyphs for
+    #[asset(path = "DejaVuSans.ttf")]
+    pub fallback_font: Handle<Font>,
 }
 #[derive(AssetCollection, Resource)]
 pub struct AudioAssets {
@@ -30,6 +108,8 @@ pub struct AudioAssets {
     pub trick: Handle<AudioSource>,
     #[asset(path = "combine-racers-bonk.ogg")]
     pub bonk: Handle<AudioSource>,
+    #[asset(path = "ui-click.wav")]
+    pub click: Handle<AudioSource>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -39,13 +119,20 @@ const EXPECTED_PIPELINES: usize = 14;
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
+        app.add_loading_state(
+            LoadingState::new(GameState::Loading)
+                .load_collection::<GameAssets>()
+                .load_collection::<AudioAssets>()
+                .continue_to_state(GameState::Decorating),
+        );
+
+        // Without a renderer, there are no pipelines to wait for.
+        if app.world().contains_resource::<Headless>() {
+            app.add_systems(OnEnter(GameState::Pipelines), skip_pipelines);
+            return;
+        }
+
         app.add_plugins(PipelinesReadyPlugin)
-            .add_loading_state(
-                LoadingState::new(GameState::Loading)
-                    .load_collection::<GameAssets>()
-                    .load_collection::<AudioAssets>()
-                    .continue_to_state(GameState::Decorating),
-            )
             .add_systems(
                 Update,
                 (
@@ -65,6 +152,10 @@ fn setup_pipelines(mut commands: Commands) {
     ));
 }
 
+fn skip_pipelines(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::MainMenu);
+}
+
 fn check_pipelines(ready: Res<PipelinesReady>, mut next_state: ResMut<NextState<GameState>>) {
     if ready.get() >= EXPECTED_PIPELINES {
         next_state.set(GameState::MainMenu);
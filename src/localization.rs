@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bevy::{prelude::*, ui::widget::measure_text_system};
+
+use crate::{loading::GameAssets, settings::LanguageSetting};
+
+/// The strings for each language. English is complete, and is used for anything
+/// missing from the others.
+const STRINGS: &[(LanguageSetting, &str)] = &[
+    (
+        LanguageSetting::English,
+        include_str!("../assets/lang/en.lang"),
+    ),
+    (
+        LanguageSetting::Spanish,
+        include_str!("../assets/lang/es.lang"),
+    ),
+    (
+        LanguageSetting::German,
+        include_str!("../assets/lang/de.lang"),
+    ),
+    (
+        LanguageSetting::French,
+        include_str!("../assets/lang/fr.lang"),
+    ),
+];
+
+/// The characters that `NanumPenScript-Tweaked.ttf` has glyphs for, from its cmap
+/// table. The tweaked font only kept the characters needed for English.
+const GAME_FONT_COVERAGE: &[(char, char)] = &[
+    ('\u{0000}', '\u{007E}'),
+    ('\u{00A0}', '\u{00A5}'),
+    ('\u{00A7}', '\u{00AE}'),
+    ('\u{00B0}', '\u{00B4}'),
+    ('\u{00B6}', '\u{00BE}'),
+    ('\u{00D7}', '\u{00D7}'),
+    ('\u{00F7}', '\u{00F8}'),
+    ('\u{2013}', '\u{2013}'),
+    ('\u{226A}', '\u{226B}'),
+    ('\u{30FB}', '\u{30FB}'),
+    ('\u{FFE0}', '\u{FFE2}'),
+    ('\u{FFE5}', '\u{FFE5}'),
+];
+
+pub struct LocalizationPlugin;
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Localization>()
+            .add_systems(
+                PreUpdate,
+                update_language.run_if(resource_exists::<LanguageSetting>),
+            )
+            .add_systems(
+                PostUpdate,
+                pick_fonts
+                    .before(measure_text_system)
+                    .run_if(resource_exists::<GameAssets>),
+            );
+    }
+}
+
+/// The UI text for the current language.
+#[derive(Resource)]
+pub struct Localization {
+    language: LanguageSetting,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+impl Default for Localization {
+    fn default() -> Self {
+        Self::new(LanguageSetting::default())
+    }
+}
+impl Localization {
+    pub fn new(language: LanguageSetting) -> Self {
+        let strings = parse(strings_file(&language));
+        let fallback = parse(strings_file(&LanguageSetting::English));
+
+        let missing = fallback
+            .keys()
+            .filter(|key| !strings.contains_key(*key))
+            .count();
+        if missing > 0 {
+            warn!("{} is missing {} strings", language, missing);
+        }
+
+        Self {
+            language,
+            strings,
+            fallback,
+        }
+    }
+
+    /// Returns the text for `key`, or the key itself if no language has it.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+
+    /// Returns the text for `key`, with `{0}`, `{1}`... replaced by `args`.
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        let mut text = self.get(key).to_string();
+        for (i, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+        }
+        text
+    }
+
+    /// The font for UI text. Text that it doesn't have glyphs for, like most accented
+    /// letters, is switched to the fallback font by `pick_fonts`.
+    pub fn font(&self, assets: &GameAssets) -> Handle<Font> {
+        assets.font.clone()
+    }
+
+    /// The game font if it has glyphs for all of `text`, or the fallback font.
+    pub fn font_for(&self, text: &str, assets: &GameAssets) -> Handle<Font> {
+        if game_font_covers(text) {
+            self.font(assets)
+        } else {
+            assets.fallback_font.clone()
+        }
+    }
+}
+
+fn strings_file(language: &LanguageSetting) -> &'static str {
+    STRINGS
+        .iter()
+        .find(|(l, _)| l == language)
+        .map(|(_, strings)| *strings)
+        .unwrap_or_default()
+}
+
+/// Reads `key = value` lines. Blank lines and lines starting with `#` are skipped,
+/// and `\n` in a value is a line break.
+fn parse(file: &str) -> HashMap<String, String> {
+    file.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let Some((key, value)) = line.split_once('=') else {
+                warn!("skipping malformed line in strings file: {}", line);
+                return None;
+            };
+            Some((key.trim().to_string(), value.trim().replace("\\n", "\n")))
+        })
+        .collect()
+}
+
+fn game_font_covers(text: &str) -> bool {
+    text.chars().all(|c| {
+        GAME_FONT_COVERAGE
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&c))
+    })
+}
+
+fn update_language(language: Res<LanguageSetting>, mut localization: ResMut<Localization>) {
+    if !language.is_changed() || localization.language == *language {
+        return;
+    }
+
+    info!("switching language to {}", *language);
+
+    *localization = Localization::new(language.clone());
+}
+
+/// Picks the font for each piece of UI text as it's spawned or changed, so that only
+/// the text that needs it loses the game font.
+fn pick_fonts(
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    mut query: Query<&mut Text, Changed<Text>>,
+) {
+    for mut text in &mut query {
+        // The text was just changed, so it'll be laid out again anyway.
+        for section in &mut text.bypass_change_detection().sections {
+            if section.style.font != assets.font && section.style.font != assets.fallback_font {
+                continue;
+            }
+
+            section.style.font = localization.font_for(&section.value, &assets);
+        }
+    }
+}
+
+/// A run condition that is true when the language changed since the last time it was
+/// checked. The first check only remembers the current language.
+pub fn language_changed(
+    localization: Res<Localization>,
+    mut last: Local<Option<LanguageSetting>>,
+) -> bool {
+    let changed = last.as_ref().is_some_and(|l| *l != localization.language);
+    *last = Some(localization.language.clone());
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_font_coverage() {
+        assert!(game_font_covers("Back flip x2!"));
+        assert!(game_font_covers("12.3 km/h"));
+        assert!(!game_font_covers("Rückwärtssalto"));
+        assert!(!game_font_covers("méga"));
+    }
+
+    #[test]
+    fn every_language_has_every_string() {
+        let english = parse(strings_file(&LanguageSetting::English));
+
+        for language in LanguageSetting::ALL {
+            let strings = parse(strings_file(language));
+            for key in english.keys() {
+                assert!(strings.contains_key(key), "{} is missing {}", language, key);
+            }
+        }
+    }
+}
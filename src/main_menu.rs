@@ -1,15 +1,13 @@
-use bevy::{
-    audio::{AudioSink, Volume},
-    pbr::{DirectionalLightShadowMap, ShadowFilteringMethod},
-    prelude::*,
-};
+use bevy::prelude::*;
 use bevy_alt_ui_navigation_lite::prelude::*;
 
 use crate::{
-    loading::{AudioAssets, GameAssets},
-    settings::{MusicSetting, SfxSetting, ShadowSetting},
-    ui::{buttons, BUTTON_TEXT, CONTAINER_BACKGROUND, NORMAL_BUTTON},
-    GameState, MainCamera, MusicController,
+    loading::GameAssets,
+    localization::Localization,
+    settings::PlayerNameSetting,
+    settings_menu::SettingsMenu,
+    ui::{buttons, Palette},
+    GameState,
 };
 
 pub struct MainMenuPlugin;
@@ -17,17 +15,24 @@ impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<TipIndex>()
             .add_systems(OnEnter(GameState::MainMenu), setup_menu)
-            .add_systems(OnExit(GameState::Pipelines), start_music)
             .add_systems(
                 Update,
                 (
-                    sfx_volume,
-                    music_volume,
-                    shadow_changed,
+                    player_name_changed,
                     button_actions,
                     buttons.after(NavRequestSystem),
                 )
-                    .run_if(in_state(GameState::MainMenu)),
+                    .run_if(in_state(GameState::MainMenu).and_then(in_state(SettingsMenu::Closed))),
+            )
+            // Hide the menu behind the settings screen, and rebuild it afterwards in case
+            // the language or colors changed.
+            .add_systems(
+                OnEnter(SettingsMenu::Open),
+                cleanup_menu.run_if(in_state(GameState::MainMenu)),
+            )
+            .add_systems(
+                OnExit(SettingsMenu::Open),
+                setup_menu.run_if(in_state(GameState::MainMenu)),
             )
             .add_systems(OnExit(GameState::MainMenu), cleanup_menu);
     }
@@ -39,19 +44,9 @@ struct MainMenuMarker;
 #[derive(Component)]
 struct PlayButton;
 #[derive(Component)]
-struct MusicSettingButton;
-#[derive(Component)]
-struct MusicSettingButtonText;
-#[derive(Component)]
-struct SfxSettingButton;
-
-#[derive(Component)]
-struct SfxSettingButtonText;
-#[derive(Component)]
-struct ShadowSettingButton;
-
+struct NameButton;
 #[derive(Component)]
-struct ShadowSettingButtonText;
+struct NameButtonText;
 #[derive(Component)]
 struct TipText;
 #[derive(Resource, Default, Deref, DerefMut)]
@@ -70,24 +65,21 @@ impl TipIndex {
 }
 
 const TIPS: &[&str] = &[
-    "Jump and rotate at the same time to do flips!",
-    "Earn even more boost by doing a different trick than the last.",
-    "Press escape or select to start over.",
-    "Do a double flip for an even longer boost!",
-    "Be careful not to bonk your head.",
-    "Get a mega-boost by submitting a 5 star rating*",
+    "tip.0", "tip.1", "tip.2", "tip.3", "tip.4", "tip.5", "tip.6", "tip.7",
 ];
 
 fn setup_menu(
     mut commands: Commands,
     assets: Res<GameAssets>,
-    sfx: Res<SfxSetting>,
-    music: Res<MusicSetting>,
-    shadow: Res<ShadowSetting>,
+    player_name: Res<PlayerNameSetting>,
     mut tip_index: ResMut<TipIndex>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
 ) {
     info!("setup_menu");
 
+    let font = localization.font(&assets);
+
     let button_style = Style {
         width: Val::Px(250.0),
         height: Val::Px(45.0),
@@ -97,19 +89,14 @@ fn setup_menu(
         ..default()
     };
     let button_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 30.0,
-        color: BUTTON_TEXT,
+        color: palette.button_text,
     };
     let title_text_style = TextStyle {
-        font: assets.font.clone(),
+        font: font.clone(),
         font_size: 60.0,
-        color: BUTTON_TEXT,
-    };
-    let subtitle_text_style = TextStyle {
-        font: assets.font.clone(),
-        font_size: 40.0,
-        color: BUTTON_TEXT,
+        color: palette.button_text,
     };
 
     let container = commands
@@ -122,7 +109,7 @@ fn setup_menu(
                     padding: UiRect::all(Val::Px(20.)),
                     ..default()
                 },
-                background_color: CONTAINER_BACKGROUND.into(),
+                background_color: palette.container_background.into(),
                 ..default()
             },
             MainMenuMarker,
@@ -145,7 +132,7 @@ fn setup_menu(
         .spawn((
             ButtonBundle {
                 style: button_style.clone(),
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
@@ -153,81 +140,67 @@ fn setup_menu(
             PlayButton,
         ))
         .with_children(|parent| {
-            parent.spawn(TextBundle::from_section("Play", button_text_style.clone()));
+            parent.spawn(TextBundle::from_section(
+                localization.get("menu.play"),
+                button_text_style.clone(),
+            ));
         })
         .id();
 
-    let audio_settings_title = commands
-        .spawn(
-            TextBundle::from_section("Audio", subtitle_text_style.clone()).with_style(Style {
-                margin: UiRect::all(Val::Px(10.0)),
-                ..default()
-            }),
-        )
-        .id();
-
-    let sfx_button = commands
+    let name_button = commands
         .spawn((
             ButtonBundle {
                 style: button_style.clone(),
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
-            MenuButton::Sfx,
-            SfxSettingButton,
+            MenuButton::Name,
+            NameButton,
         ))
         .with_children(|parent| {
             parent.spawn((
-                TextBundle::from_section(format!("SFX {}%", **sfx), button_text_style.clone()),
-                SfxSettingButtonText,
+                TextBundle::from_section(
+                    player_name.0.clone().unwrap_or_default(),
+                    button_text_style.clone(),
+                ),
+                NameButtonText,
             ));
         })
         .id();
 
-    let music_button = commands
+    let achievements_button = commands
         .spawn((
             ButtonBundle {
                 style: button_style.clone(),
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
-            MenuButton::Music,
-            MusicSettingButton,
+            MenuButton::Achievements,
         ))
         .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_section(format!("Music {}%", **music), button_text_style.clone()),
-                MusicSettingButtonText,
+            parent.spawn(TextBundle::from_section(
+                localization.get("menu.achievements"),
+                button_text_style.clone(),
             ));
         })
         .id();
 
-    let shadow_settings_title = commands
-        .spawn(
-            TextBundle::from_section("Shadows", subtitle_text_style).with_style(Style {
-                margin: UiRect::all(Val::Px(10.0)),
-                ..default()
-            }),
-        )
-        .id();
-
-    let shadow_button = commands
+    let settings_button = commands
         .spawn((
             ButtonBundle {
                 style: button_style,
-                background_color: NORMAL_BUTTON.into(),
+                background_color: palette.normal_button.into(),
                 ..default()
             },
             Focusable::default(),
-            MenuButton::Shadow,
-            ShadowSettingButton,
+            MenuButton::Settings,
         ))
         .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_section(format!("{}", *shadow), button_text_style),
-                ShadowSettingButtonText,
+            parent.spawn(TextBundle::from_section(
+                localization.get("menu.settings"),
+                button_text_style,
             ));
         })
         .id();
@@ -235,11 +208,9 @@ fn setup_menu(
     commands.entity(container).push_children(&[
         title,
         play_button,
-        audio_settings_title,
-        sfx_button,
-        music_button,
-        shadow_settings_title,
-        shadow_button,
+        name_button,
+        achievements_button,
+        settings_button,
     ]);
 
     commands
@@ -267,11 +238,11 @@ fn setup_menu(
             parent.spawn((
                 TextBundle {
                     text: Text::from_section(
-                        TIPS[tip_index.next()].to_owned(),
+                        localization.get(TIPS[tip_index.next()]).to_owned(),
                         TextStyle {
-                            font: assets.font.clone(),
+                            font: font.clone(),
                             font_size: 40.0,
-                            color: Color::WHITE,
+                            color: palette.title_text,
                         },
                     ),
                     ..Default::default()
@@ -284,9 +255,9 @@ fn setup_menu(
 #[derive(Component)]
 enum MenuButton {
     Play,
-    Sfx,
-    Music,
-    Shadow,
+    Name,
+    Achievements,
+    Settings,
 }
 
 // Seems like bevy-ui-navigation forces us to write this abomination of a megasystem
@@ -294,14 +265,7 @@ fn button_actions(
     buttons: Query<&MenuButton>,
     mut events: EventReader<NavEvent>,
     mut next_state: ResMut<NextState<GameState>>,
-    mut music_setting: ResMut<MusicSetting>,
-    mut text_queries: ParamSet<(
-        Query<&mut Text, With<SfxSettingButtonText>>,
-        Query<&mut Text, With<MusicSettingButtonText>>,
-        Query<&mut Text, With<ShadowSettingButtonText>>,
-    )>,
-    mut sfx_setting: ResMut<SfxSetting>,
-    mut shadow_setting: ResMut<ShadowSetting>,
+    mut next_settings: ResMut<NextState<SettingsMenu>>,
 ) {
     // Note: we have a closure here because the `buttons` query is mutable.
     // for immutable queries, you can use `.activated_in_query` which returns an iterator.
@@ -312,123 +276,29 @@ fn button_actions(
             MenuButton::Play => {
                 next_state.set(GameState::Playing);
             }
-            MenuButton::Sfx => {
-                if **sfx_setting == 0 {
-                    **sfx_setting = 100;
-                } else {
-                    **sfx_setting -= 10;
-                }
-
-                for mut text in text_queries.p0().iter_mut() {
-                    text.sections[0].value = format!("SFX {}%", **sfx_setting);
-                }
+            MenuButton::Name => {
+                next_state.set(GameState::NameEntry);
             }
-            MenuButton::Music => {
-                if **music_setting == 0 {
-                    **music_setting = 100;
-                } else {
-                    **music_setting -= 10;
-                }
-
-                for mut text in text_queries.p1().iter_mut() {
-                    text.sections[0].value = format!("Music {}%", **music_setting);
-                }
+            MenuButton::Achievements => {
+                next_state.set(GameState::Achievements);
             }
-            MenuButton::Shadow => {
-                *shadow_setting = shadow_setting.next();
-
-                for mut text in text_queries.p2().iter_mut() {
-                    text.sections[0].value = format!("{}", *shadow_setting);
-                }
+            MenuButton::Settings => {
+                next_settings.set(SettingsMenu::Open);
             }
         }
     }
 }
 
-fn sfx_volume(mut commands: Commands, sfx_setting: Res<SfxSetting>, game_audio: Res<AudioAssets>) {
-    // Do not run when SfxSetting is first added by SavePlugin
-    if !sfx_setting.is_changed() || sfx_setting.is_added() {
-        return;
-    }
-
-    commands.spawn(AudioBundle {
-        source: game_audio.trick.clone(),
-        settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(**sfx_setting as f32 / 100.)),
-    });
-}
-
-fn music_volume(
-    music_setting: Res<MusicSetting>,
-    music_query: Query<&AudioSink, With<MusicController>>,
-) {
-    // Do not run when MusicSetting is first added by SavePlugin
-    if !music_setting.is_changed() || music_setting.is_added() {
-        return;
-    }
-
-    for sink in &music_query {
-        sink.set_volume(**music_setting as f32 / 100.)
-    }
-}
-
-fn start_music(
-    mut commands: Commands,
-    audio_assets: Res<AudioAssets>,
-    music_setting: Res<MusicSetting>,
+fn player_name_changed(
+    player_name: Res<PlayerNameSetting>,
+    mut text_query: Query<&mut Text, With<NameButtonText>>,
 ) {
-    commands.spawn((
-        AudioBundle {
-            source: audio_assets.music.clone(),
-            settings: PlaybackSettings::LOOP
-                .with_volume(Volume::new(**music_setting as f32 / 100.)),
-        },
-        MusicController,
-    ));
-}
-
-fn shadow_changed(
-    mut commands: Commands,
-    shadow_setting: Res<ShadowSetting>,
-    camera_query: Query<Entity, With<MainCamera>>,
-    mut light_query: Query<&mut DirectionalLight>,
-) {
-    // Do run when ShadowSetting is first added by SavePlugin
-    if !shadow_setting.is_changed() {
+    if !player_name.is_changed() {
         return;
     }
 
-    let mut light = light_query.single_mut();
-    let camera_entity = camera_query.single();
-
-    match *shadow_setting {
-        ShadowSetting::None => {
-            light.shadows_enabled = false;
-            commands
-                .entity(camera_entity)
-                .insert(ShadowFilteringMethod::Hardware2x2);
-            commands.insert_resource(DirectionalLightShadowMap { size: 256 });
-        }
-        ShadowSetting::Low => {
-            light.shadows_enabled = true;
-            commands
-                .entity(camera_entity)
-                .insert(ShadowFilteringMethod::Hardware2x2);
-            commands.insert_resource(DirectionalLightShadowMap { size: 256 });
-        }
-        ShadowSetting::Medium => {
-            light.shadows_enabled = true;
-            commands
-                .entity(camera_entity)
-                .insert(ShadowFilteringMethod::Gaussian);
-            commands.insert_resource(DirectionalLightShadowMap { size: 512 });
-        }
-        ShadowSetting::High => {
-            light.shadows_enabled = true;
-            commands
-                .entity(camera_entity)
-                .insert(ShadowFilteringMethod::Gaussian);
-            commands.insert_resource(DirectionalLightShadowMap { size: 1024 });
-        }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = player_name.0.clone().unwrap_or_default();
     }
 }
 
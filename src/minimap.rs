@@ -0,0 +1,347 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    loading::{checkpoints, GameAssets, TRACK_NAME},
+    localization::Localization,
+    ui::{GameUiMarker, Palette},
+    AfterPhysics, FinishLine, GameSet, GameState, Player, Track,
+};
+
+/// The number of columns in the minimap's elevation profile.
+const SAMPLES: usize = 80;
+const WIDTH_PX: f32 = 400.;
+const HEIGHT_PX: f32 = 50.;
+const ICON_PX: f32 = 8.;
+
+pub struct MinimapPlugin;
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrackExtent>()
+            .add_systems(OnEnter(GameState::Playing), (measure_track, spawn).chain())
+            .add_systems(
+                PostUpdate,
+                (spawn_icons, update_minimap)
+                    .chain()
+                    .after(GameSet::Movement)
+                    .run_if(in_state(GameState::Playing))
+                    .in_set(AfterPhysics),
+            );
+    }
+}
+
+/// Shows up on the minimap. Anything racing along the track should have one.
+#[derive(Component)]
+pub struct MinimapIcon {
+    pub color: Color,
+}
+
+/// The size and shape of the track, as seen from the side.
+#[derive(Resource, Default)]
+pub struct TrackExtent {
+    min: Vec3,
+    max: Vec3,
+    /// Where the race starts.
+    start: f32,
+    finish: f32,
+    checkpoints: Vec<f32>,
+    /// The height of the track at evenly spaced points between `min.x` and `max.x`.
+    profile: Vec<f32>,
+}
+impl TrackExtent {
+    /// Where a point is on the minimap, from the bottom left corner.
+    fn normalize(&self, position: Vec3) -> Vec2 {
+        let size = (self.max - self.min).truncate().max(Vec2::ONE);
+        ((position - self.min).truncate() / size).clamp(Vec2::ZERO, Vec2::ONE)
+    }
+
+    /// How far along the course `x` is, from the start to the finish line.
+    pub fn progress(&self, x: f32) -> f32 {
+        if self.finish == self.start {
+            return 0.;
+        }
+
+        ((x - self.start) / (self.finish - self.start)).clamp(0., 1.)
+    }
+}
+
+#[derive(Component)]
+struct ProfileColumn(usize);
+#[derive(Component)]
+struct MinimapIconNode(Entity);
+#[derive(Component)]
+struct MinimapIcons;
+#[derive(Component)]
+struct ProgressText;
+
+/// Finds the bounds of a mesh in world space.
+fn world_bounds(aabb: &Aabb, transform: &GlobalTransform) -> (Vec3, Vec3) {
+    let center = Vec3::from(aabb.center);
+    let half = Vec3::from(aabb.half_extents);
+
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+    for corner in [
+        Vec3::new(-1., -1., -1.),
+        Vec3::new(-1., -1., 1.),
+        Vec3::new(-1., 1., -1.),
+        Vec3::new(-1., 1., 1.),
+        Vec3::new(1., -1., -1.),
+        Vec3::new(1., -1., 1.),
+        Vec3::new(1., 1., -1.),
+        Vec3::new(1., 1., 1.),
+    ] {
+        let point = transform.transform_point(center + half * corner);
+        min = min.min(point);
+        max = max.max(point);
+    }
+
+    (min, max)
+}
+
+fn measure_track(
+    mut extent: ResMut<TrackExtent>,
+    track_query: Query<(&Aabb, &GlobalTransform), With<Track>>,
+    finish_query: Query<(&Aabb, &GlobalTransform), With<FinishLine>>,
+    rapier_context: Res<RapierContext>,
+) {
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+    for (aabb, transform) in &track_query {
+        let (track_min, track_max) = world_bounds(aabb, transform);
+        min = min.min(track_min);
+        max = max.max(track_max);
+    }
+
+    if min.x > max.x {
+        warn!("Couldn't find the track for the minimap");
+        *extent = TrackExtent::default();
+        return;
+    }
+
+    let center_x = |(aabb, transform): (&Aabb, &GlobalTransform)| {
+        let (min, max) = world_bounds(aabb, transform);
+        (min.x + max.x) / 2.
+    };
+
+    // The player starts at the origin.
+    let start = 0.;
+    let finish = finish_query.iter().next().map(center_x).unwrap_or(max.x);
+
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+    let profile = (0..SAMPLES)
+        .map(|i| {
+            let x = min.x + (max.x - min.x) * (i as f32 + 0.5) / SAMPLES as f32;
+            rapier_context
+                .cast_ray(
+                    Vec3::new(x, max.y + 1., 0.),
+                    Vec3::NEG_Y,
+                    max.y - min.y + 2.,
+                    true,
+                    filter,
+                )
+                .map(|(_, toi)| max.y + 1. - toi)
+                .unwrap_or(min.y)
+        })
+        .collect();
+
+    *extent = TrackExtent {
+        min,
+        max,
+        start,
+        finish,
+        checkpoints: checkpoints(TRACK_NAME)
+            .iter()
+            .map(|checkpoint| checkpoint.x)
+            .collect(),
+        profile,
+    };
+}
+
+fn spawn(
+    mut commands: Commands,
+    extent: Res<TrackExtent>,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    if extent.profile.is_empty() {
+        return;
+    }
+
+    let column_width = WIDTH_PX / SAMPLES as f32;
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(5.),
+                    width: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            GameUiMarker,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(WIDTH_PX),
+                            height: Val::Px(HEIGHT_PX),
+                            align_items: AlignItems::FlexEnd,
+                            ..default()
+                        },
+                        background_color: palette.container_background.with_alpha(0.6).into(),
+                        ..default()
+                    },
+                    MinimapIcons,
+                ))
+                .with_children(|parent| {
+                    for (i, height) in extent.profile.iter().enumerate() {
+                        let x = extent.min.x
+                            + (extent.max.x - extent.min.x) * (i as f32 + 0.5) / SAMPLES as f32;
+                        let y = extent.normalize(Vec3::new(x, *height, 0.)).y;
+
+                        parent.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(column_width),
+                                    height: Val::Percent(y * 100.),
+                                    ..default()
+                                },
+                                background_color: palette.locked_text.into(),
+                                ..default()
+                            },
+                            ProfileColumn(i),
+                        ));
+                    }
+
+                    // Checkpoints and the finish line
+                    let lines = extent
+                        .checkpoints
+                        .iter()
+                        .map(|x| (*x, palette.locked_text))
+                        .chain([(extent.finish, palette.title_text)]);
+                    for (x, color) in lines {
+                        let left = extent.normalize(Vec3::new(x, 0., 0.)).x;
+
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Percent(left * 100.),
+                                width: Val::Px(2.),
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        });
+                    }
+                });
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: localization.font(&assets),
+                        font_size: 24.0,
+                        color: palette.title_text,
+                    },
+                ),
+                ProgressText,
+            ));
+        });
+}
+
+/// Adds a marker to the minimap for everything that should be on it.
+fn spawn_icons(
+    mut commands: Commands,
+    icon_query: Query<(Entity, &MinimapIcon)>,
+    node_query: Query<(Entity, &MinimapIconNode)>,
+    minimap_query: Query<Entity, With<MinimapIcons>>,
+) {
+    let Ok(minimap) = minimap_query.get_single() else {
+        return;
+    };
+
+    for (node, MinimapIconNode(entity)) in &node_query {
+        if !icon_query.contains(*entity) {
+            commands.entity(node).despawn_recursive();
+        }
+    }
+
+    for (entity, icon) in &icon_query {
+        if node_query.iter().any(|(_, node)| node.0 == entity) {
+            continue;
+        }
+
+        let node = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(ICON_PX),
+                        height: Val::Px(ICON_PX),
+                        margin: UiRect {
+                            left: Val::Px(-ICON_PX / 2.),
+                            bottom: Val::Px(-ICON_PX / 2.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    background_color: icon.color.into(),
+                    z_index: ZIndex::Local(1),
+                    ..default()
+                },
+                MinimapIconNode(entity),
+            ))
+            .id();
+        commands.entity(minimap).add_child(node);
+    }
+}
+
+fn update_minimap(
+    extent: Res<TrackExtent>,
+    racer_query: Query<(&Transform, Has<Player>), With<MinimapIcon>>,
+    mut node_query: Query<(&mut Style, &MinimapIconNode)>,
+    mut column_query: Query<(&mut BackgroundColor, &ProfileColumn)>,
+    mut text_query: Query<&mut Text, With<ProgressText>>,
+    palette: Res<Palette>,
+) {
+    for (mut style, MinimapIconNode(entity)) in &mut node_query {
+        let Ok((transform, _)) = racer_query.get(*entity) else {
+            continue;
+        };
+
+        let position = extent.normalize(transform.translation);
+        style.left = Val::Percent(position.x * 100.);
+        style.bottom = Val::Percent(position.y * 100.);
+    }
+
+    let Some((player_transform, _)) = racer_query.iter().find(|(_, player)| *player) else {
+        return;
+    };
+    let player_x = player_transform.translation.x;
+
+    // Fill in the part of the track that's behind us.
+    let covered = extent.normalize(player_transform.translation).x * SAMPLES as f32;
+    for (mut color, ProfileColumn(i)) in &mut column_query {
+        let target = if (*i as f32) < covered {
+            palette.title_text
+        } else {
+            palette.locked_text
+        };
+        if color.0 != target {
+            color.0 = target;
+        }
+    }
+
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("{:.0}%", extent.progress(player_x) * 100.);
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::{audio::Volume, ecs::system::SystemParam, prelude::*};
+use bevy_alt_ui_navigation_lite::prelude::*;
+
+use crate::{
+    loading::AudioAssets,
+    settings::{MasterVolumeSetting, MusicSetting, MutedBusesSetting, SfxSetting, UiVolumeSetting},
+};
+
+pub struct MixerPlugin;
+impl Plugin for MixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Mixer>()
+            .add_systems(
+                PreUpdate,
+                (update_mixer, apply_mixer)
+                    .chain()
+                    .run_if(resource_exists::<MutedBusesSetting>),
+            )
+            .add_systems(
+                Update,
+                ui_sounds
+                    .after(NavRequestSystem)
+                    .run_if(resource_exists::<AudioAssets>),
+            );
+    }
+}
+
+/// A group of sounds that share a volume setting. Everything that plays goes through
+/// one of these, and then through `Master`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBus {
+    Master,
+    Music,
+    Sfx,
+    Ui,
+}
+impl AudioBus {
+    /// The localization key of the bus's name.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Master => "audio.master",
+            Self::Music => "audio.music",
+            Self::Sfx => "audio.sfx",
+            Self::Ui => "audio.ui",
+        }
+    }
+}
+
+/// A sound playing through the mixer, and its volume before the mixer is applied.
+#[derive(Component)]
+pub struct MixerChannel {
+    pub bus: AudioBus,
+    pub volume: f32,
+}
+
+/// The overall volume of each bus, after the master volume and muting.
+#[derive(Resource, Default)]
+pub struct Mixer {
+    music: f32,
+    sfx: f32,
+    ui: f32,
+}
+impl Mixer {
+    pub fn gain(&self, bus: AudioBus) -> f32 {
+        match bus {
+            // Sounds are never played on the master bus directly, so this is only
+            // here for completeness.
+            AudioBus::Master => 1.,
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ui => self.ui,
+        }
+    }
+
+    /// Plays a sound once through `bus`.
+    pub fn play(&self, commands: &mut Commands, source: Handle<AudioSource>, bus: AudioBus) {
+        self.play_with_volume(commands, source, bus, 1.);
+    }
+
+    pub fn play_with_volume(
+        &self,
+        commands: &mut Commands,
+        source: Handle<AudioSource>,
+        bus: AudioBus,
+        volume: f32,
+    ) {
+        commands.spawn((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new(volume * self.gain(bus))),
+            },
+            MixerChannel { bus, volume },
+        ));
+    }
+}
+
+/// The volume settings for every bus.
+#[derive(SystemParam)]
+pub struct VolumeSettings<'w> {
+    master: ResMut<'w, MasterVolumeSetting>,
+    music: ResMut<'w, MusicSetting>,
+    sfx: ResMut<'w, SfxSetting>,
+    ui: ResMut<'w, UiVolumeSetting>,
+    pub muted: ResMut<'w, MutedBusesSetting>,
+}
+impl VolumeSettings<'_> {
+    pub fn volume(&self, bus: AudioBus) -> u8 {
+        match bus {
+            AudioBus::Master => **self.master,
+            AudioBus::Music => **self.music,
+            AudioBus::Sfx => **self.sfx,
+            AudioBus::Ui => **self.ui,
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.master.is_changed()
+            || self.music.is_changed()
+            || self.sfx.is_changed()
+            || self.ui.is_changed()
+            || self.muted.is_changed()
+    }
+
+    /// Turns a bus up or down by `offset` steps of 10%, staying between 0% and 100%.
+    pub fn adjust_volume(&mut self, bus: AudioBus, offset: isize) {
+        let volume = match bus {
+            AudioBus::Master => &mut **self.master,
+            AudioBus::Music => &mut **self.music,
+            AudioBus::Sfx => &mut **self.sfx,
+            AudioBus::Ui => &mut **self.ui,
+        };
+
+        *volume = (*volume as isize + offset * 10).clamp(0, 100) as u8;
+    }
+
+    /// Puts every bus back to its default volume, unmuted.
+    pub fn reset(&mut self) {
+        self.master.set_if_neq(default());
+        self.music.set_if_neq(default());
+        self.sfx.set_if_neq(default());
+        self.ui.set_if_neq(default());
+        self.muted.set_if_neq(default());
+    }
+}
+
+fn update_mixer(
+    master: Res<MasterVolumeSetting>,
+    music: Res<MusicSetting>,
+    sfx: Res<SfxSetting>,
+    ui: Res<UiVolumeSetting>,
+    muted: Res<MutedBusesSetting>,
+    mut mixer: ResMut<Mixer>,
+) {
+    if !master.is_changed()
+        && !music.is_changed()
+        && !sfx.is_changed()
+        && !ui.is_changed()
+        && !muted.is_changed()
+    {
+        return;
+    }
+
+    let volume = |setting: u8, bus: AudioBus| {
+        if muted.is_muted(bus) {
+            0.
+        } else {
+            setting as f32 / 100.
+        }
+    };
+
+    let master = volume(**master, AudioBus::Master);
+
+    *mixer = Mixer {
+        music: master * volume(**music, AudioBus::Music),
+        sfx: master * volume(**sfx, AudioBus::Sfx),
+        ui: master * volume(**ui, AudioBus::Ui),
+    };
+}
+
+/// Keeps sounds that are already playing up to date with the mixer, and with their
+/// own volume when that changes.
+fn apply_mixer(mixer: Res<Mixer>, query: Query<(Ref<MixerChannel>, &AudioSink)>) {
+    for (channel, sink) in &query {
+        if mixer.is_changed() || channel.is_changed() {
+            sink.set_volume(channel.volume * mixer.gain(channel.bus));
+        }
+    }
+}
+
+fn ui_sounds(
+    mut commands: Commands,
+    mut events: EventReader<NavEvent>,
+    mixer: Res<Mixer>,
+    audio_assets: Res<AudioAssets>,
+) {
+    for event in events.read() {
+        match event {
+            NavEvent::FocusChanged { .. } => {
+                mixer.play_with_volume(
+                    &mut commands,
+                    audio_assets.click.clone(),
+                    AudioBus::Ui,
+                    0.5,
+                );
+            }
+            NavEvent::NoChanges {
+                request: NavRequest::Action,
+                ..
+            } => {
+                mixer.play(&mut commands, audio_assets.click.clone(), AudioBus::Ui);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::{audio::Volume, prelude::*};
+
+use crate::{
+    loading::AudioAssets,
+    mixer::{AudioBus, Mixer, MixerChannel},
+    FinishedEvent, GameState, RaceTime,
+};
+
+/// How long it takes for the music to fade all the way from one volume to another.
+const FADE_SECONDS: f32 = 1.5;
+
+pub struct MusicPlugin;
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Pipelines), start_music)
+            .add_systems(Update, update_music.run_if(resource_exists::<AudioAssets>));
+    }
+}
+
+#[derive(Component)]
+struct Music;
+
+/// What's going on in the game, as far as the music is concerned.
+///
+/// The soundtrack is a single mix, so it can only follow the mood by getting louder
+/// or quieter. Layering in more of the song while boosting or on the final stretch
+/// would need the song split into stems.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MusicMood {
+    Menu,
+    Countdown,
+    Racing,
+    Results,
+}
+impl MusicMood {
+    fn volume(&self) -> f32 {
+        match self {
+            Self::Menu => 0.6,
+            Self::Countdown => 0.4,
+            Self::Racing => 1.0,
+            Self::Results => 0.5,
+        }
+    }
+}
+
+fn start_music(mut commands: Commands, audio_assets: Res<AudioAssets>, mixer: Res<Mixer>) {
+    let volume = MusicMood::Menu.volume();
+
+    commands.spawn((
+        AudioBundle {
+            source: audio_assets.music.clone(),
+            settings: PlaybackSettings::LOOP
+                .with_volume(Volume::new(volume * mixer.gain(AudioBus::Music))),
+        },
+        MixerChannel {
+            bus: AudioBus::Music,
+            volume,
+        },
+        Music,
+    ));
+}
+
+fn current_mood(state: &GameState, race_time: &RaceTime, finished: bool) -> MusicMood {
+    match state {
+        GameState::Playing if finished => MusicMood::Results,
+        GameState::Playing if race_time.paused() && race_time.elapsed_secs() == 0. => {
+            MusicMood::Countdown
+        }
+        GameState::Playing => MusicMood::Racing,
+        GameState::Leaderboard | GameState::GameOver => MusicMood::Results,
+        _ => MusicMood::Menu,
+    }
+}
+
+fn update_music(
+    time: Res<Time<Real>>,
+    state: Res<State<GameState>>,
+    race_time: Res<RaceTime>,
+    mut finished_events: EventReader<FinishedEvent>,
+    mut music_query: Query<&mut MixerChannel, With<Music>>,
+) {
+    let finished = finished_events.read().count() > 0;
+    let target = current_mood(state.get(), &race_time, finished).volume();
+
+    let step = time.delta_seconds() / FADE_SECONDS;
+
+    for mut channel in &mut music_query {
+        if channel.volume != target {
+            channel.volume += (target - channel.volume).clamp(-step, step);
+        }
+    }
+}
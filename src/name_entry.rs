@@ -0,0 +1,427 @@
+use bevy::prelude::*;
+use bevy_alt_ui_navigation_lite::prelude::*;
+
+use crate::{
+    loading::GameAssets,
+    localization::Localization,
+    random_name::random_name,
+    settings::{LeaderboardSetting, PlayerNameSetting},
+    ui::{buttons, Palette},
+    GameState,
+};
+
+pub const MAX_NAME_LEN: usize = 24;
+
+const KEY_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl-", "zxcvbnm_."];
+
+pub struct NameEntryPlugin;
+impl Plugin for NameEntryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NameEntry>()
+            .add_systems(OnEnter(GameState::NameEntry), (init_entry, spawn).chain())
+            .add_systems(
+                Update,
+                (
+                    button_actions,
+                    update_name_text,
+                    update_key_labels,
+                    buttons.after(NavRequestSystem),
+                )
+                    .run_if(in_state(GameState::NameEntry)),
+            )
+            .add_systems(OnExit(GameState::NameEntry), cleanup);
+    }
+}
+
+#[derive(Resource, Default)]
+struct NameEntry {
+    name: String,
+    shift: bool,
+    error: Option<&'static str>,
+    /// Done was pressed once for a name that starts a new leaderboard profile.
+    confirm_new_profile: bool,
+}
+
+#[derive(Component)]
+struct NameEntryMarker;
+#[derive(Component)]
+struct NameText;
+#[derive(Component)]
+struct ErrorText;
+#[derive(Component)]
+struct KeyLabel(char);
+
+#[derive(Component)]
+enum KeyButton {
+    Char(char),
+    Shift,
+    Space,
+    Backspace,
+    Random,
+    Cancel,
+    Done,
+}
+
+/// Checks that a name will display properly and isn't blank. Errors are the
+/// localization key of the message.
+pub fn validate_name(name: &str) -> Result<(), &'static str> {
+    if name.trim().is_empty() {
+        return Err("name.empty");
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err("name.too_long");
+    }
+    if name.trim() != name || name.contains("  ") {
+        return Err("name.extra_spaces");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))
+    {
+        return Err("name.unsupported");
+    }
+
+    Ok(())
+}
+
+fn init_entry(mut entry: ResMut<NameEntry>, player_name: Res<PlayerNameSetting>) {
+    *entry = NameEntry {
+        name: player_name.0.clone().unwrap_or_default(),
+        ..default()
+    };
+}
+
+fn spawn(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    entry: Res<NameEntry>,
+    palette: Res<Palette>,
+) {
+    let font = localization.font(&assets);
+
+    let title_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 60.0,
+        color: palette.title_text,
+    };
+    let key_style = Style {
+        width: Val::Px(45.0),
+        height: Val::Px(45.0),
+        margin: UiRect::all(Val::Px(3.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let wide_key_style = Style {
+        width: Val::Px(120.0),
+        ..key_style.clone()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.button_text,
+    };
+
+    let root = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                ..default()
+            },
+            NameEntryMarker,
+        ))
+        .id();
+
+    let container = commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            background_color: palette.container_background.into(),
+            ..default()
+        })
+        .id();
+
+    let title = commands
+        .spawn(
+            TextBundle::from_section(localization.get("name.title"), title_text_style).with_style(
+                Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ),
+        )
+        .id();
+
+    let name = commands
+        .spawn((
+            TextBundle::from_section(
+                format!("{}_", entry.name),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: palette.title_text,
+                },
+            ),
+            NameText,
+        ))
+        .id();
+
+    let error = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: palette.slower_text,
+                },
+            )
+            .with_style(Style {
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+            ErrorText,
+        ))
+        .id();
+
+    let mut rows = vec![];
+
+    for keys in KEY_ROWS {
+        let row = commands
+            .spawn(NodeBundle::default())
+            .with_children(|parent| {
+                for c in keys.chars() {
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: key_style.clone(),
+                                background_color: palette.normal_button.into(),
+                                ..default()
+                            },
+                            Focusable::default(),
+                            KeyButton::Char(c),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(c.to_string(), button_text_style.clone()),
+                                KeyLabel(c),
+                            ));
+                        });
+                }
+            })
+            .id();
+
+        rows.push(row);
+    }
+
+    let special_rows = [
+        vec![
+            ("name.shift", KeyButton::Shift),
+            ("name.space", KeyButton::Space),
+            ("name.delete", KeyButton::Backspace),
+        ],
+        vec![
+            ("name.random", KeyButton::Random),
+            ("name.cancel", KeyButton::Cancel),
+            ("name.done", KeyButton::Done),
+        ],
+    ];
+
+    for keys in special_rows {
+        let row = commands
+            .spawn(NodeBundle::default())
+            .with_children(|parent| {
+                for (label, key) in keys {
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: wide_key_style.clone(),
+                                background_color: palette.normal_button.into(),
+                                ..default()
+                            },
+                            Focusable::default(),
+                            key,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                localization.get(label),
+                                button_text_style.clone(),
+                            ));
+                        });
+                }
+            })
+            .id();
+
+        rows.push(row);
+    }
+
+    commands.entity(root).push_children(&[container]);
+
+    commands
+        .entity(container)
+        .push_children(&[title, name, error])
+        .push_children(&rows);
+}
+
+fn button_actions(
+    buttons: Query<&KeyButton>,
+    mut events: EventReader<NavEvent>,
+    mut entry: ResMut<NameEntry>,
+    mut player_name: ResMut<PlayerNameSetting>,
+    leaderboard_setting: Res<LeaderboardSetting>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for button in events.nav_iter().activated_in_query(&buttons) {
+        let confirming = std::mem::take(&mut entry.confirm_new_profile);
+        entry.error = None;
+
+        match button {
+            KeyButton::Char(c) => {
+                if entry.name.chars().count() < MAX_NAME_LEN {
+                    let c = if entry.shift {
+                        c.to_ascii_uppercase()
+                    } else {
+                        *c
+                    };
+                    entry.name.push(c);
+                    entry.shift = false;
+                }
+            }
+            KeyButton::Shift => {
+                entry.shift = !entry.shift;
+            }
+            KeyButton::Space => {
+                if entry.name.chars().count() < MAX_NAME_LEN {
+                    entry.name.push(' ');
+                    entry.shift = true;
+                }
+            }
+            KeyButton::Backspace => {
+                entry.name.pop();
+            }
+            KeyButton::Random => {
+                entry.name = random_name();
+            }
+            KeyButton::Cancel => {
+                next_state.set(GameState::MainMenu);
+            }
+            KeyButton::Done => match validate_name(&entry.name) {
+                // Jornet can't rename a player, so a new name means a new player
+                // without any of the old one's scores.
+                Ok(())
+                    if !confirming
+                        && leaderboard_setting
+                            .as_ref()
+                            .is_some_and(|player| player.name != entry.name) =>
+                {
+                    entry.error = Some("name.new_profile");
+                    entry.confirm_new_profile = true;
+                }
+                Ok(()) => {
+                    info!("changing player name to {:?}", entry.name);
+
+                    **player_name = Some(entry.name.clone());
+                    next_state.set(GameState::MainMenu);
+                }
+                Err(error) => {
+                    entry.error = Some(error);
+                }
+            },
+        }
+    }
+}
+
+fn update_name_text(
+    entry: Res<NameEntry>,
+    mut name_query: Query<&mut Text, (With<NameText>, Without<ErrorText>)>,
+    mut error_query: Query<&mut Text, (With<ErrorText>, Without<NameText>)>,
+    localization: Res<Localization>,
+) {
+    if !entry.is_changed() {
+        return;
+    }
+
+    for mut text in name_query.iter_mut() {
+        text.sections[0].value = format!("{}_", entry.name);
+    }
+
+    for mut text in error_query.iter_mut() {
+        text.sections[0].value = entry
+            .error
+            .map(|error| localization.get(error))
+            .unwrap_or_default()
+            .to_string();
+    }
+}
+
+fn update_key_labels(entry: Res<NameEntry>, mut query: Query<(&mut Text, &KeyLabel)>) {
+    if !entry.is_changed() {
+        return;
+    }
+
+    for (mut text, label) in query.iter_mut() {
+        let c = if entry.shift {
+            label.0.to_ascii_uppercase()
+        } else {
+            label.0
+        };
+        text.sections[0].value = c.to_string();
+    }
+}
+
+fn cleanup(mut commands: Commands, query: Query<Entity, With<NameEntryMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in [
+            "Rob",
+            "Dr. Combine",
+            "x_y-z 2",
+            "a".repeat(MAX_NAME_LEN).as_str(),
+        ] {
+            assert_eq!(validate_name(name), Ok(()), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert_eq!(validate_name(""), Err("name.empty"));
+        assert_eq!(validate_name("   "), Err("name.empty"));
+        assert_eq!(
+            validate_name(&"a".repeat(MAX_NAME_LEN + 1)),
+            Err("name.too_long")
+        );
+        assert_eq!(validate_name(" Rob"), Err("name.extra_spaces"));
+        assert_eq!(validate_name("Rob "), Err("name.extra_spaces"));
+        assert_eq!(validate_name("Dr.  Rob"), Err("name.extra_spaces"));
+        assert_eq!(validate_name("Röb"), Err("name.unsupported"));
+        assert_eq!(validate_name("Rob!"), Err("name.unsupported"));
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_rapier3d::prelude::Velocity;
+use rand::Rng;
+
+use crate::{ui::Palette, Boost, GameState, Player, Wheel, WheelsOnGround, BOOST_SPEED_LIMIT};
+
+/// Stop emitting new particles when there are this many around.
+const MAX_PARTICLES: usize = 600;
+/// Dust particles per second from each wheel at full speed.
+const DUST_RATE: f32 = 40.;
+/// Exhaust particles per second while boosting.
+const EXHAUST_RATE: f32 = 50.;
+/// Where the exhaust comes out, relative to the player.
+const EXHAUST_OFFSET: Vec3 = Vec3::new(-2.5, 0.5, 0.);
+
+pub struct ParticlesPlugin;
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                (wheel_dust, boost_exhaust).run_if(in_state(GameState::Playing)),
+            )
+            // Let any remaining particles play out on the leaderboard and game over screens
+            .add_systems(PostUpdate, update_particles);
+    }
+}
+
+#[derive(Resource)]
+struct ParticleAssets {
+    mesh: Handle<Mesh>,
+    dust: Handle<StandardMaterial>,
+    flame: Handle<StandardMaterial>,
+    smoke: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec3,
+    /// How quickly the particle slows down, per second.
+    drag: f32,
+    /// How much the particle grows over its lifetime.
+    growth: f32,
+    size: f32,
+    lifetime: Timer,
+}
+
+/// Dust and exhaust are emitted a fraction of a particle at a time.
+#[derive(Default)]
+struct Emission(f32);
+impl Emission {
+    /// Adds `amount` particles and returns the number of whole ones that are ready.
+    fn emit(&mut self, amount: f32) -> usize {
+        self.0 += amount;
+        let whole = self.0.floor();
+        self.0 -= whole;
+        whole as usize
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(Sphere::new(0.5).mesh().ico(1).unwrap()),
+        dust: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.55, 0.45, 0.3),
+            perceptual_roughness: 1.,
+            ..default()
+        }),
+        flame: materials.add(StandardMaterial {
+            base_color: Color::srgb(1., 0.55, 0.1),
+            emissive: LinearRgba::rgb(4., 1.5, 0.2),
+            unlit: true,
+            ..default()
+        }),
+        smoke: materials.add(StandardMaterial {
+            // Always the default boost color, as the particles are part of the world
+            base_color: Palette::DEFAULT.boosted_text,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    mesh: &Handle<Mesh>,
+    material: &Handle<StandardMaterial>,
+    position: Vec3,
+    particle: Particle,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: mesh.clone(),
+            material: material.clone(),
+            transform: Transform::from_translation(position).with_scale(Vec3::splat(particle.size)),
+            ..default()
+        },
+        NotShadowCaster,
+        particle,
+    ));
+}
+
+fn wheel_dust(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<ParticleAssets>,
+    player_query: Query<(&WheelsOnGround, &Velocity, &Children), With<Player>>,
+    wheel_query: Query<&GlobalTransform, With<Wheel>>,
+    particle_query: Query<(), With<Particle>>,
+    mut emission: Local<Emission>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (wheels, velocity, children) in &player_query {
+        if **wheels == 0 {
+            continue;
+        }
+
+        let speed = velocity.linvel.length();
+        let intensity = (speed / BOOST_SPEED_LIMIT).min(1.);
+        if intensity < 0.1 {
+            continue;
+        }
+
+        let count = emission.emit(DUST_RATE * intensity * time.delta_seconds());
+        if count == 0 || particle_query.iter().len() > MAX_PARTICLES {
+            continue;
+        }
+
+        for transform in wheel_query.iter_many(children) {
+            // Kick up dust from where the wheel meets the track
+            let contact = transform.translation() - Vec3::Y;
+
+            for _ in 0..count {
+                let kick = Vec3::new(
+                    -velocity.linvel.x * rng.gen_range(0.05..0.2),
+                    rng.gen_range(1.0..4.0) * intensity,
+                    rng.gen_range(-2.0..2.0),
+                );
+
+                spawn_particle(
+                    &mut commands,
+                    &assets.mesh,
+                    &assets.dust,
+                    contact,
+                    Particle {
+                        velocity: kick,
+                        drag: 2.,
+                        growth: 2.5,
+                        size: rng.gen_range(0.3..0.6) * (0.5 + intensity),
+                        lifetime: Timer::from_seconds(rng.gen_range(0.4..0.9), TimerMode::Once),
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn boost_exhaust(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<ParticleAssets>,
+    player_query: Query<(&Boost, &Velocity, &GlobalTransform), With<Player>>,
+    particle_query: Query<(), With<Particle>>,
+    mut emission: Local<Emission>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (boost, velocity, transform) in &player_query {
+        if boost.remaining <= 0. {
+            continue;
+        }
+
+        let count = emission.emit(EXHAUST_RATE * time.delta_seconds());
+        if count == 0 || particle_query.iter().len() > MAX_PARTICLES {
+            continue;
+        }
+
+        let exhaust = transform.transform_point(EXHAUST_OFFSET);
+        let backwards = *transform.left();
+
+        for _ in 0..count {
+            // Mostly flames, with a trail of boost-colored smoke
+            let flame = rng.gen_bool(0.6);
+
+            spawn_particle(
+                &mut commands,
+                &assets.mesh,
+                if flame { &assets.flame } else { &assets.smoke },
+                exhaust,
+                Particle {
+                    // Leave the puffs behind rather than carrying them along with the player
+                    velocity: velocity.linvel * 0.3
+                        + backwards * rng.gen_range(4.0..8.0)
+                        + Vec3::new(0., rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)),
+                    drag: 4.,
+                    growth: if flame { -0.8 } else { 2. },
+                    size: rng.gen_range(0.4..0.8),
+                    lifetime: Timer::from_seconds(
+                        if flame {
+                            rng.gen_range(0.15..0.3)
+                        } else {
+                            rng.gen_range(0.4..0.7)
+                        },
+                        TimerMode::Once,
+                    ),
+                },
+            );
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+    let elapsed = time.delta_seconds();
+    if elapsed == 0. {
+        return;
+    }
+
+    for (entity, mut particle, mut transform) in &mut query {
+        particle.lifetime.tick(time.delta());
+        if particle.lifetime.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let drag = (-particle.drag * elapsed).exp();
+        particle.velocity *= drag;
+        transform.translation += particle.velocity * elapsed;
+
+        // Grow or shrink over time, then shrink away to nothing at the very end
+        let t = particle.lifetime.fraction();
+        let fade = (particle.lifetime.fraction_remaining() * 4.).min(1.);
+        let scale = particle.size * (1. + particle.growth * t).max(0.) * fade;
+        transform.scale = Vec3::splat(scale);
+    }
+}
@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy_alt_ui_navigation_lite::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    freeze::Freeze,
+    loading::GameAssets,
+    localization::Localization,
+    photo_mode::photo_mode_inactive,
+    settings_menu::SettingsMenu,
+    ui::{buttons, Palette},
+    Action, GameState, Player,
+};
+
+pub struct PauseMenuPlugin;
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseMenu>()
+            .add_systems(
+                Update,
+                toggle.run_if(
+                    in_state(GameState::Playing)
+                        .and_then(photo_mode_inactive)
+                        .and_then(in_state(SettingsMenu::Closed)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (button_actions, buttons.after(NavRequestSystem))
+                    .after(toggle)
+                    .run_if(
+                        in_state(GameState::Playing)
+                            .and_then(pause_menu_active)
+                            .and_then(in_state(SettingsMenu::Closed)),
+                    ),
+            )
+            // Hide the menu behind the settings screen
+            .add_systems(OnEnter(SettingsMenu::Open), despawn_menu)
+            .add_systems(
+                OnExit(SettingsMenu::Open),
+                respawn_menu.run_if(pause_menu_active),
+            )
+            .add_systems(OnExit(GameState::Playing), resume_on_exit);
+    }
+}
+
+/// Freezes the race while the player changes settings or takes a break.
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    active: bool,
+    /// Whether `RaceTime` was running when the game was paused.
+    race_was_running: bool,
+}
+
+pub fn pause_menu_active(pause_menu: Res<PauseMenu>) -> bool {
+    pause_menu.active
+}
+
+pub fn pause_menu_inactive(pause_menu: Res<PauseMenu>) -> bool {
+    !pause_menu.active
+}
+
+#[derive(Component)]
+struct PauseMenuMarker;
+
+#[derive(Component)]
+enum PauseMenuButton {
+    Resume,
+    Settings,
+    Quit,
+}
+
+/// Everything that pausing and resuming changes.
+#[derive(bevy::ecs::system::SystemParam)]
+struct PauseParams<'w, 's> {
+    commands: Commands<'w, 's>,
+    freeze: Freeze<'w, 's>,
+    menu_query: Query<'w, 's, Entity, With<PauseMenuMarker>>,
+}
+
+fn toggle(
+    mut pause_menu: ResMut<PauseMenu>,
+    mut params: PauseParams,
+    action_query: Query<&ActionState<Action>, With<Player>>,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    let Ok(action_state) = action_query.get_single() else {
+        return;
+    };
+    if !action_state.just_pressed(&Action::Pause) {
+        return;
+    }
+
+    if pause_menu.active {
+        resume(&mut pause_menu, &mut params);
+        return;
+    }
+
+    info!("pausing");
+
+    *pause_menu = PauseMenu {
+        active: true,
+        race_was_running: params.freeze.freeze(),
+    };
+
+    spawn_menu(&mut params.commands, &assets, &localization, &palette);
+}
+
+fn resume(pause_menu: &mut PauseMenu, params: &mut PauseParams) {
+    if !pause_menu.active {
+        return;
+    }
+
+    info!("resuming");
+
+    params.freeze.unfreeze(pause_menu.race_was_running);
+
+    for entity in &params.menu_query {
+        params.commands.entity(entity).despawn_recursive();
+    }
+
+    pause_menu.active = false;
+}
+
+fn resume_on_exit(mut pause_menu: ResMut<PauseMenu>, mut params: PauseParams) {
+    resume(&mut pause_menu, &mut params);
+}
+
+fn spawn_menu(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    localization: &Localization,
+    palette: &Palette,
+) {
+    let font = localization.font(assets);
+
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(45.0),
+        margin: UiRect::all(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.button_text,
+    };
+    let title_text_style = TextStyle {
+        font,
+        font_size: 60.0,
+        color: palette.title_text,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            PauseMenuMarker,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        margin: UiRect::all(Val::Auto),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.)),
+                        ..default()
+                    },
+                    background_color: palette.container_background.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(localization.get("pause.title"), title_text_style)
+                            .with_style(Style {
+                                margin: UiRect {
+                                    bottom: Val::Px(10.0),
+                                    ..default()
+                                },
+                                ..default()
+                            }),
+                    );
+
+                    for (button, key) in [
+                        (PauseMenuButton::Resume, "pause.resume"),
+                        (PauseMenuButton::Settings, "menu.settings"),
+                        (PauseMenuButton::Quit, "pause.quit"),
+                    ] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: palette.normal_button.into(),
+                                    ..default()
+                                },
+                                Focusable::default(),
+                                button,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    localization.get(key),
+                                    button_text_style.clone(),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn despawn_menu(mut commands: Commands, query: Query<Entity, With<PauseMenuMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn respawn_menu(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    spawn_menu(&mut commands, &assets, &localization, &palette);
+}
+
+fn button_actions(
+    buttons: Query<&PauseMenuButton>,
+    mut events: EventReader<NavEvent>,
+    mut pause_menu: ResMut<PauseMenu>,
+    mut params: PauseParams,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_settings: ResMut<NextState<SettingsMenu>>,
+) {
+    for button in events.nav_iter().activated_in_query(&buttons) {
+        match button {
+            PauseMenuButton::Resume => {
+                resume(&mut pause_menu, &mut params);
+            }
+            PauseMenuButton::Settings => {
+                next_settings.set(SettingsMenu::Open);
+            }
+            PauseMenuButton::Quit => {
+                // Like resetting, this ends the race without a time
+                pause_menu.race_was_running = false;
+                next_state.set(GameState::GameOver);
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::view::screenshot::ScreenshotManager,
+    utils::SystemTime,
+    window::PrimaryWindow,
+};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    freeze::Freeze,
+    loading::GameAssets,
+    localization::Localization,
+    pause_menu::pause_menu_inactive,
+    ui::{GameUiMarker, Palette},
+    Action, GameState, MainCamera, Player,
+};
+
+const ORBIT_SPEED: f32 = 0.005;
+const KEY_ORBIT_SPEED: f32 = 1.5;
+const PAN_SPEED: f32 = 0.002;
+const KEY_PAN_SPEED: f32 = 0.5;
+const ZOOM_SPEED: f32 = 0.1;
+const FOV_SPEED: f32 = 0.5;
+const MIN_DISTANCE: f32 = 5.;
+const MAX_DISTANCE: f32 = 300.;
+const MIN_FOV: f32 = 0.1;
+const MAX_FOV: f32 = 2.5;
+
+pub struct PhotoModePlugin;
+impl Plugin for PhotoModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhotoMode>()
+            .add_systems(
+                Update,
+                toggle.run_if(in_state(GameState::Playing).and_then(pause_menu_inactive)),
+            )
+            .add_systems(
+                Update,
+                (orbit_camera, adjust_fov, toggle_help, screenshot)
+                    .after(toggle)
+                    .run_if(in_state(GameState::Playing).and_then(photo_mode_active)),
+            )
+            .add_systems(OnExit(GameState::Playing), leave_on_exit);
+    }
+}
+
+/// Freezes the race so that the player can take pictures of it.
+#[derive(Resource, Default)]
+pub struct PhotoMode {
+    active: bool,
+    /// Whether `RaceTime` was running when photo mode started.
+    race_was_running: bool,
+    /// The camera's transform and field of view before photo mode started.
+    saved_camera: Option<(Transform, f32)>,
+    focus: Vec3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+pub fn photo_mode_active(photo_mode: Res<PhotoMode>) -> bool {
+    photo_mode.active
+}
+
+pub fn photo_mode_inactive(photo_mode: Res<PhotoMode>) -> bool {
+    !photo_mode.active
+}
+
+#[derive(Component)]
+struct PhotoModeHelp;
+
+/// Everything that photo mode changes while it's active.
+#[derive(bevy::ecs::system::SystemParam)]
+struct PhotoModeParams<'w, 's> {
+    commands: Commands<'w, 's>,
+    freeze: Freeze<'w, 's>,
+    camera_query:
+        Query<'w, 's, (&'static mut Transform, &'static mut Projection), With<MainCamera>>,
+    ui_query: Query<'w, 's, &'static mut Visibility, With<GameUiMarker>>,
+    help_query: Query<'w, 's, Entity, With<PhotoModeHelp>>,
+}
+
+fn toggle(
+    mut photo_mode: ResMut<PhotoMode>,
+    mut params: PhotoModeParams,
+    action_query: Query<&ActionState<Action>, With<Player>>,
+    player_query: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    let Ok(action_state) = action_query.get_single() else {
+        return;
+    };
+    if !action_state.just_pressed(&Action::PhotoMode) {
+        return;
+    }
+
+    if photo_mode.active {
+        leave(&mut photo_mode, &mut params);
+        return;
+    }
+
+    info!("entering photo mode");
+
+    let Ok((mut camera_transform, projection)) = params.camera_query.get_single_mut() else {
+        return;
+    };
+    let Projection::Perspective(perspective) = projection.into_inner() else {
+        return;
+    };
+
+    let focus = player_query
+        .get_single()
+        .map(|transform| transform.translation)
+        .unwrap_or_default();
+
+    *photo_mode = PhotoMode {
+        active: true,
+        race_was_running: params.freeze.freeze(),
+        saved_camera: Some((*camera_transform, perspective.fov)),
+        focus,
+        yaw: 0.,
+        pitch: 0.,
+        distance: (camera_transform.translation - focus).length(),
+    };
+    *camera_transform = orbit_transform(&photo_mode);
+
+    for mut visibility in &mut params.ui_query {
+        *visibility = Visibility::Hidden;
+    }
+
+    params.commands.spawn((
+        TextBundle::from_section(
+            localization.get("photo_mode.help"),
+            TextStyle {
+                font: localization.font(&assets),
+                font_size: 24.0,
+                color: palette.title_text,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(5.),
+            bottom: Val::Px(5.),
+            ..default()
+        }),
+        PhotoModeHelp,
+    ));
+}
+
+fn leave(photo_mode: &mut PhotoMode, params: &mut PhotoModeParams) {
+    if !photo_mode.active {
+        return;
+    }
+
+    info!("leaving photo mode");
+
+    if let (Some((transform, fov)), Ok((mut camera_transform, mut projection))) = (
+        photo_mode.saved_camera.take(),
+        params.camera_query.get_single_mut(),
+    ) {
+        *camera_transform = transform;
+        if let Projection::Perspective(perspective) = &mut *projection {
+            perspective.fov = fov;
+        }
+    }
+
+    params.freeze.unfreeze(photo_mode.race_was_running);
+
+    for mut visibility in &mut params.ui_query {
+        *visibility = Visibility::Inherited;
+    }
+
+    for entity in &params.help_query {
+        params.commands.entity(entity).despawn_recursive();
+    }
+
+    photo_mode.active = false;
+}
+
+fn leave_on_exit(mut photo_mode: ResMut<PhotoMode>, mut params: PhotoModeParams) {
+    leave(&mut photo_mode, &mut params);
+}
+
+fn orbit_transform(photo_mode: &PhotoMode) -> Transform {
+    let rotation = Quat::from_euler(EulerRot::YXZ, photo_mode.yaw, photo_mode.pitch, 0.);
+
+    Transform::from_translation(photo_mode.focus + rotation * Vec3::Z * photo_mode.distance)
+        .looking_at(photo_mode.focus, Vec3::Y)
+}
+
+fn orbit_camera(
+    time: Res<Time<Real>>,
+    mut photo_mode: ResMut<PhotoMode>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    let elapsed = time.delta_seconds();
+    let motion: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    let scroll: f32 = mouse_wheel.read().map(|wheel| wheel.y).sum();
+
+    let mut orbit = Vec2::ZERO;
+    let mut pan = Vec2::ZERO;
+    let mut zoom = -scroll;
+
+    if mouse_buttons.pressed(MouseButton::Left) {
+        orbit += motion * ORBIT_SPEED;
+    }
+    if mouse_buttons.pressed(MouseButton::Right) || mouse_buttons.pressed(MouseButton::Middle) {
+        pan += motion * PAN_SPEED;
+    }
+
+    for (key, direction) in [(KeyCode::KeyQ, Vec2::NEG_X), (KeyCode::KeyE, Vec2::X)] {
+        if keys.pressed(key) {
+            orbit += direction * KEY_ORBIT_SPEED * elapsed;
+        }
+    }
+    for (key, direction) in [
+        (KeyCode::ArrowLeft, Vec2::X),
+        (KeyCode::ArrowRight, Vec2::NEG_X),
+        (KeyCode::ArrowUp, Vec2::Y),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+    ] {
+        if keys.pressed(key) {
+            pan += direction * KEY_PAN_SPEED * elapsed;
+        }
+    }
+    if keys.pressed(KeyCode::Minus) {
+        zoom += 10. * elapsed;
+    }
+    if keys.pressed(KeyCode::Equal) {
+        zoom -= 10. * elapsed;
+    }
+
+    if orbit == Vec2::ZERO && pan == Vec2::ZERO && zoom == 0. {
+        return;
+    }
+
+    photo_mode.yaw -= orbit.x;
+    photo_mode.pitch = (photo_mode.pitch - orbit.y).clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+    photo_mode.distance =
+        (photo_mode.distance * (1. + zoom * ZOOM_SPEED)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+
+    let Ok(mut camera_transform) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    // Pan in the plane of the screen, faster when further away.
+    let distance = photo_mode.distance;
+    photo_mode.focus +=
+        (camera_transform.left() * pan.x + camera_transform.up() * pan.y) * distance;
+
+    *camera_transform = orbit_transform(&photo_mode);
+}
+
+fn adjust_fov(
+    time: Res<Time<Real>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut projection_query: Query<&mut Projection, With<MainCamera>>,
+) {
+    let mut change = 0.;
+    if keys.pressed(KeyCode::BracketLeft) {
+        change -= FOV_SPEED * time.delta_seconds();
+    }
+    if keys.pressed(KeyCode::BracketRight) {
+        change += FOV_SPEED * time.delta_seconds();
+    }
+    if change == 0. {
+        return;
+    }
+
+    for mut projection in &mut projection_query {
+        if let Projection::Perspective(perspective) = &mut *projection {
+            perspective.fov = (perspective.fov + change).clamp(MIN_FOV, MAX_FOV);
+        }
+    }
+}
+
+fn toggle_help(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut help_query: Query<&mut Visibility, With<PhotoModeHelp>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyH) {
+        return;
+    }
+
+    for mut visibility in &mut help_query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn screenshot(
+    keys: Res<ButtonInput<KeyCode>>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+) {
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = format!("combine-racers-{}.png", timestamp);
+
+    info!("saving screenshot to {}", path);
+
+    if let Err(e) = screenshot_manager.save_screenshot_to_disk(window, path) {
+        warn!("failed to take screenshot: {}", e);
+    }
+}
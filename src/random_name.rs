@@ -1,12 +1,17 @@
-use rand::seq::SliceRandom;
+use rand::seq::{IteratorRandom, SliceRandom};
+
+use crate::name_entry::MAX_NAME_LEN;
 
 pub fn random_name() -> String {
     let mut rng = rand::thread_rng();
-    format!(
-        "{} {}",
-        ADJECTIVES.choose(&mut rng).unwrap(),
-        OG_POKEMON.choose(&mut rng).unwrap(),
-    )
+    let pokemon = OG_POKEMON.choose(&mut rng).unwrap();
+    // Only adjectives that leave room for the pokemon, so the name passes validation
+    let adjective = ADJECTIVES
+        .iter()
+        .filter(|adjective| adjective.len() + 1 + pokemon.len() <= MAX_NAME_LEN)
+        .choose(&mut rng)
+        .unwrap();
+    format!("{} {}", adjective, pokemon)
 }
 
 const OG_POKEMON: &[&str] = &[
@@ -1833,3 +1838,24 @@ const ADJECTIVES: &[&str] = &[
     "Zesty",
     "Zigzag",
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::name_entry::validate_name;
+
+    #[test]
+    fn random_names_are_valid() {
+        for _ in 0..1000 {
+            let name = random_name();
+            assert_eq!(validate_name(&name), Ok(()), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn every_word_is_valid() {
+        for word in ADJECTIVES.iter().chain(OG_POKEMON) {
+            assert_eq!(validate_name(word), Ok(()), "{:?}", word);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Recordings of the player's input, sent along with scores to leaderboards that can
+//! check them.
+//!
+//! A replay holds the input and frame time of every frame in `GameState::Playing`,
+//! which is enough to run the race again and check that the claimed time is real.
+//! That is done by `src/bin/verify_replay.rs`, which exits successfully only if the
+//! replay finishes in the time it claims.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+use crate::{
+    loading::TRACK_NAME, read_player_input, GameSet, GameState, Player, PlayerInput, RaceTime,
+    TUNING_VERSION,
+};
+
+/// Bump this when changing the encoding below.
+const REPLAY_FORMAT: u32 = 1;
+/// How far the simulated race time may drift from the claimed time.
+const VERIFY_TOLERANCE: f32 = 0.05;
+/// How long we'll keep simulating after the input runs out before giving up.
+const VERIFY_GRACE_FRAMES: usize = 600;
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(OnEnter(GameState::Playing), start_recording)
+            .add_systems(
+                Update,
+                record
+                    .after(GameSet::Input)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Runs the game without a window, feeding it the input from a replay instead of
+/// the player. See `run`.
+pub struct VerifyReplayPlugin {
+    pub replay: Replay,
+}
+impl Plugin for VerifyReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Playback {
+            replay: self.replay.clone(),
+            cursor: 0,
+        })
+        .add_systems(OnEnter(GameState::MainMenu), skip_main_menu)
+        .add_systems(
+            Update,
+            (
+                play_back
+                    .in_set(GameSet::Input)
+                    .after(read_player_input)
+                    .run_if(in_state(GameState::Playing)),
+                give_up.run_if(in_state(GameState::Playing)),
+            ),
+        )
+        .add_systems(Last, set_frame_time)
+        .add_systems(OnEnter(GameState::Leaderboard), check_finish)
+        .add_systems(OnEnter(GameState::GameOver), report_game_over);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReplayFrame {
+    delta_nanos: u32,
+    input: u8,
+}
+
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    frames: Vec<ReplayFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub track: String,
+    pub tuning: u32,
+    /// The race time that this replay claims to finish in.
+    pub time: f32,
+    frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn from_recording(recorder: &ReplayRecorder, time: f32) -> Self {
+        Self {
+            track: TRACK_NAME.to_string(),
+            tuning: TUNING_VERSION,
+            time,
+            frames: recorder.frames.clone(),
+        }
+    }
+
+    /// Encodes the replay as `<format>:<track>:<tuning>:<time>:<frames>`, where frames
+    /// is base64 of five bytes per frame: the frame time in little-endian nanoseconds
+    /// followed by the `PlayerInput` bits.
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(self.frames.len() * 5);
+        for frame in &self.frames {
+            bytes.extend_from_slice(&frame.delta_nanos.to_le_bytes());
+            bytes.push(frame.input);
+        }
+
+        format!(
+            "{}:{}:{}:{}:{}",
+            REPLAY_FORMAT,
+            self.track,
+            self.tuning,
+            self.time,
+            base64_encode(&bytes)
+        )
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, &'static str> {
+        let mut parts = encoded.trim().split(':');
+
+        let format: u32 = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or("missing replay format")?;
+        if format != REPLAY_FORMAT {
+            return Err("unsupported replay format");
+        }
+
+        let track = parts.next().ok_or("missing track")?.to_string();
+        let tuning = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or("missing tuning version")?;
+        let time = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or("missing time")?;
+        let bytes = base64_decode(parts.next().ok_or("missing frames")?)?;
+
+        if parts.next().is_some() {
+            return Err("unexpected data after frames");
+        }
+        if bytes.len() % 5 != 0 {
+            return Err("truncated frames");
+        }
+
+        let frames = bytes
+            .chunks_exact(5)
+            .map(|chunk| ReplayFrame {
+                delta_nanos: u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                input: chunk[4],
+            })
+            .collect();
+
+        Ok(Self {
+            track,
+            tuning,
+            time,
+            frames,
+        })
+    }
+
+    /// Checks the replay against a race that was simulated from it and finished in
+    /// `simulated` seconds.
+    fn verify(&self, simulated: f32) -> Result<(), String> {
+        if self.track != TRACK_NAME {
+            Err(format!("recorded on unknown track {:?}", self.track))
+        } else if self.tuning != TUNING_VERSION {
+            Err(format!(
+                "recorded with tuning version {}, but this is version {}",
+                self.tuning, TUNING_VERSION
+            ))
+        } else if (simulated - self.time).abs() > VERIFY_TOLERANCE {
+            Err(format!(
+                "claimed {:.3}s, but finished in {:.3}s",
+                self.time, simulated
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn start_recording(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.frames.clear();
+}
+
+fn record(
+    time: Res<Time>,
+    query: Query<&PlayerInput, With<Player>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    // Nothing happens while time is paused for photo mode.
+    if time.delta().is_zero() {
+        return;
+    }
+
+    let input = query.get_single().copied().unwrap_or_default();
+
+    recorder.frames.push(ReplayFrame {
+        delta_nanos: time.delta().as_nanos() as u32,
+        input: input.to_bits(),
+    });
+}
+
+#[derive(Resource)]
+struct Playback {
+    replay: Replay,
+    cursor: usize,
+}
+
+fn skip_main_menu(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
+fn play_back(mut playback: ResMut<Playback>, mut query: Query<&mut PlayerInput, With<Player>>) {
+    let input = playback
+        .replay
+        .frames
+        .get(playback.cursor)
+        .map(|frame| PlayerInput::from_bits(frame.input))
+        .unwrap_or_default();
+
+    for mut player_input in &mut query {
+        *player_input = input;
+    }
+
+    playback.cursor += 1;
+}
+
+/// Makes the next frame as long as the recorded one, so that physics plays out the same.
+fn set_frame_time(playback: Res<Playback>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let delta = match playback.replay.frames.get(playback.cursor) {
+        Some(frame) => Duration::from_nanos(frame.delta_nanos as u64),
+        None => Duration::from_secs_f32(1. / 60.),
+    };
+
+    *strategy = TimeUpdateStrategy::ManualDuration(delta);
+}
+
+fn give_up(playback: Res<Playback>, mut exit: EventWriter<AppExit>) {
+    if playback.cursor > playback.replay.frames.len() + VERIFY_GRACE_FRAMES {
+        error!("replay rejected: the race never finished.");
+        exit.send(AppExit::error());
+    }
+}
+
+fn check_finish(playback: Res<Playback>, race_time: Res<RaceTime>, mut exit: EventWriter<AppExit>) {
+    match playback.replay.verify(race_time.elapsed_secs()) {
+        Ok(()) => {
+            info!(
+                "replay verified: claimed {:.3}s, finished in {:.3}s.",
+                playback.replay.time,
+                race_time.elapsed_secs()
+            );
+            exit.send(AppExit::Success);
+        }
+        Err(reason) => {
+            error!("replay rejected: {}.", reason);
+            exit.send(AppExit::error());
+        }
+    }
+}
+
+fn report_game_over(mut exit: EventWriter<AppExit>) {
+    error!("replay rejected: the player died or reset.");
+    exit.send(AppExit::error());
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+fn base64_decode(encoded: &str) -> Result<Vec<u8>, &'static str> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return Err("invalid base64 length");
+        }
+
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or("invalid base64 character")?;
+            n |= (value as u32) << (18 - i * 6);
+        }
+
+        let decoded = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        bytes.extend_from_slice(&decoded[..chunk.len() - 1]);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(time: f32) -> Replay {
+        Replay {
+            track: TRACK_NAME.to_string(),
+            tuning: TUNING_VERSION,
+            time,
+            frames: vec![
+                ReplayFrame {
+                    delta_nanos: 16_666_667,
+                    input: 0b101,
+                },
+                ReplayFrame {
+                    delta_nanos: u32::MAX,
+                    input: 0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay(12.345);
+        assert_eq!(Replay::decode(&replay.encode()), Ok(replay));
+    }
+
+    #[test]
+    fn truncated_frames() {
+        let encoded = replay(12.345).encode();
+        assert!(Replay::decode(&encoded[..encoded.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn base64() {
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| i * 31).collect();
+            assert_eq!(base64_decode(&base64_encode(&bytes)), Ok(bytes));
+        }
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_encode(b"Ma"), "TWE=");
+        assert!(base64_decode("TWF!").is_err());
+    }
+
+    #[test]
+    fn verify_tolerance() {
+        let replay = replay(30.0);
+
+        assert!(replay.verify(30.0).is_ok());
+        assert!(replay.verify(30.0 + VERIFY_TOLERANCE * 0.9).is_ok());
+        assert!(replay.verify(30.0 - VERIFY_TOLERANCE * 0.9).is_ok());
+        assert!(replay.verify(30.0 + VERIFY_TOLERANCE * 1.1).is_err());
+        assert!(replay.verify(30.0 - VERIFY_TOLERANCE * 1.1).is_err());
+    }
+
+    #[test]
+    fn verify_track_and_tuning() {
+        let mut other_track = replay(30.0);
+        other_track.track = "elsewhere".to_string();
+        assert!(other_track.verify(30.0).is_err());
+
+        let mut other_tuning = replay(30.0);
+        other_tuning.tuning = TUNING_VERSION + 1;
+        assert!(other_tuning.verify(30.0).is_err());
+    }
+}
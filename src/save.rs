@@ -1,25 +1,623 @@
-use crate::settings::{LeaderboardSetting, MusicSetting, SfxSetting, ShadowSetting};
+use crate::settings::{
+    AchievementsSetting, CameraSetting, FrameRateLimitSetting, LanguageSetting, LeaderboardSetting,
+    LifetimeStatsSetting, LocalLeaderboardSetting, MasterVolumeSetting, MsaaSetting, MusicSetting,
+    MutedBusesSetting, PendingScoresSetting, PlayerNameSetting, ReducedMotionSetting,
+    RenderScaleSetting, ResolutionSetting, RunHistorySetting, SaveVersionSetting, SfxSetting,
+    ShadowSetting, TextScaleSetting, ThemeSetting, UiVolumeSetting, VsyncSetting,
+    WindowModeSetting,
+};
 
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        Struct, TypeRegistry,
+    },
+};
 use bevy_simple_prefs::{Prefs, PrefsPlugin};
 
+/// The version of `SaveFile` written by this build. Bump it and add a step to
+/// `MIGRATIONS` when a field is renamed or changes shape. Added fields don't need a
+/// migration, they're filled in with their defaults.
+pub const SAVE_VERSION: u32 = 1;
+
+/// The save's file name, or its local storage key on the web.
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_NAME: &str = "save.ron";
+#[cfg(target_arch = "wasm32")]
+const SAVE_NAME: &str = "combine-racers-save";
+
+/// The steps that bring a save up to date. The step at index `n` upgrades a save from
+/// version `n` to version `n + 1`.
+const MIGRATIONS: &[fn(&mut SaveFields)] = &[migrate_unversioned];
+
 pub struct SavePlugin;
 impl Plugin for SavePlugin {
     fn build(&self, app: &mut App) {
+        // This has to happen before `PrefsPlugin` reads the save, or a save it can't
+        // read would be replaced with the defaults.
+        app.register_type::<SaveFile>();
+        prepare_save(&app.world().resource::<AppTypeRegistry>().read());
+
         app.add_plugins(PrefsPlugin::<SaveFile> {
-            #[cfg(not(target_arch = "wasm32"))]
-            filename: "save.ron".to_string(),
-            #[cfg(target_arch = "wasm32")]
-            filename: "combine-racers-save".to_string(),
+            filename: SAVE_NAME.to_string(),
             ..default()
         });
     }
 }
 
-#[derive(Prefs, Reflect, Default)]
-struct SaveFile {
+/// Declares `SaveFile` and a `DefaultSettingsPlugin` that provides the same settings,
+/// so that the two can't drift apart.
+macro_rules! save_file {
+    ($($field:ident: $setting:ty,)*) => {
+        #[derive(Prefs, Reflect, Default)]
+        struct SaveFile {
+            $($field: $setting,)*
+        }
+
+        /// Provides the default settings without reading or writing the save file.
+        pub struct DefaultSettingsPlugin;
+        impl Plugin for DefaultSettingsPlugin {
+            fn build(&self, app: &mut App) {
+                $(app.init_resource::<$setting>();)*
+            }
+        }
+    };
+}
+
+save_file! {
+    version: SaveVersionSetting,
+    master_volume: MasterVolumeSetting,
     sfx: SfxSetting,
     music: MusicSetting,
+    ui_volume: UiVolumeSetting,
+    muted: MutedBusesSetting,
     leaderboard: LeaderboardSetting,
     shadow: ShadowSetting,
+    window_mode: WindowModeSetting,
+    resolution: ResolutionSetting,
+    vsync: VsyncSetting,
+    msaa: MsaaSetting,
+    frame_rate_limit: FrameRateLimitSetting,
+    render_scale: RenderScaleSetting,
+    camera: CameraSetting,
+    reduced_motion: ReducedMotionSetting,
+    language: LanguageSetting,
+    theme: ThemeSetting,
+    text_scale: TextScaleSetting,
+    history: RunHistorySetting,
+    player_name: PlayerNameSetting,
+    local_leaderboard: LocalLeaderboardSetting,
+    pending_scores: PendingScoresSetting,
+    lifetime_stats: LifetimeStatsSetting,
+    achievements: AchievementsSetting,
+}
+
+/// The top level fields of a save, with their values left as RON.
+///
+/// Migrating fields one at a time means that a field that can't be read only loses
+/// that one setting, rather than all of them.
+#[derive(Default)]
+struct SaveFields(Vec<(String, String)>);
+impl SaveFields {
+    fn parse(text: &str) -> Option<Self> {
+        let text = strip_comments(text)?;
+        let text = text.trim();
+
+        // Allow the struct's name before its fields
+        let start = text.find('(')?;
+        if !text[..start]
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_')
+        {
+            return None;
+        }
+        let body = text[start + 1..].strip_suffix(')')?;
+
+        let mut fields = Vec::new();
+        for entry in split_top_level(body)? {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (name, value) = entry.split_once(':')?;
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return None;
+            }
+
+            fields.push((name.to_string(), value.trim().to_string()));
+        }
+
+        Some(Self(fields))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn push(&mut self, name: &str, value: String) {
+        self.0.push((name.to_string(), value));
+    }
+
+    /// Saves from before the version field count as version 0.
+    fn version(&self) -> u32 {
+        self.get("version")
+            .and_then(|value| {
+                value
+                    .trim_start_matches('(')
+                    .trim_end_matches(')')
+                    .trim()
+                    .parse()
+                    .ok()
+            })
+            .unwrap_or(0)
+    }
+
+    fn to_ron(&self) -> String {
+        let mut ron = "(\n".to_string();
+        for (name, value) in &self.0 {
+            ron.push_str(&format!("    {}: {},\n", name, value));
+        }
+        ron.push(')');
+        ron
+    }
+}
+
+/// Removes `//` and `/* */` comments. Returns `None` if a string or comment is never
+/// closed.
+fn strip_comments(text: &str) -> Option<String> {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                stripped.push(c);
+                let mut escaped = false;
+                loop {
+                    let c = chars.next()?;
+                    stripped.push(c);
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // Keep the newline that ends the comment
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // Block comments can be nested
+                let mut depth = 1;
+                while depth > 0 {
+                    match (chars.next()?, chars.peek().copied()) {
+                        ('/', Some('*')) => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        ('*', Some('/')) => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        _ => {}
+                    }
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+
+    Some(stripped)
+}
+
+/// Splits `text` on the commas that aren't inside brackets or strings. Returns `None`
+/// if the brackets or quotes aren't balanced.
+fn split_top_level(text: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 || in_string {
+        return None;
+    }
+
+    parts.push(&text[start..]);
+    Some(parts)
+}
+
+/// Saves from before the version field. Fields were only ever added back then, so
+/// there's nothing to change.
+fn migrate_unversioned(_fields: &mut SaveFields) {}
+
+/// What [`upgrade`] made of a save.
+#[derive(Debug, PartialEq)]
+enum Upgrade {
+    Upgraded {
+        text: String,
+        /// The fields that had values that couldn't be read. They were restored from
+        /// the backup if it had them, or reset otherwise.
+        unreadable: Vec<String>,
+    },
+    /// The save is from a newer version of the game, which may have fields that this
+    /// version doesn't know about.
+    Newer(u32),
+    /// The save couldn't be read at all.
+    Corrupt,
+}
+
+/// Parses a save and runs any migrations it needs.
+fn migrated(text: &str) -> Result<SaveFields, Upgrade> {
+    let mut fields = SaveFields::parse(text).ok_or(Upgrade::Corrupt)?;
+
+    let version = fields.version();
+    if version > SAVE_VERSION {
+        return Err(Upgrade::Newer(version));
+    }
+
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("migrating save file from version {} to {}", from, from + 1);
+        migrate(&mut fields);
+    }
+
+    Ok(fields)
+}
+
+/// Brings a save up to date and checks each of its fields against `SaveFile`.
+///
+/// A field that can't be read is taken from `backup` if possible, and reset to its
+/// default otherwise. Fields that are missing get their defaults too, and fields that
+/// `SaveFile` doesn't have are dropped.
+fn upgrade(text: &str, backup: Option<&str>, registry: &TypeRegistry) -> Upgrade {
+    let fields = match migrated(text) {
+        Ok(fields) => fields,
+        Err(upgrade) => return upgrade,
+    };
+    let backup = backup.and_then(|backup| migrated(backup).ok());
+
+    let defaults = SaveFile::default();
+    let mut upgraded = SaveFields::default();
+    let mut unreadable = Vec::new();
+
+    for (i, default) in defaults.iter_fields().enumerate() {
+        let Some(name) = defaults.name_at(i) else {
+            continue;
+        };
+
+        let registration = default
+            .get_represented_type_info()
+            .and_then(|info| registry.get(info.type_id()));
+        // Without a registration there's no way to check the value, so trust it
+        let readable = |value: &str| {
+            let Some(registration) = registration else {
+                return true;
+            };
+            ron::Options::default()
+                .from_str_seed(value, TypedReflectDeserializer::new(registration, registry))
+                .is_ok()
+        };
+
+        // Always write the current version
+        if let Some(value) = fields.get(name).filter(|_| name != "version") {
+            if readable(value) {
+                upgraded.push(name, value.to_string());
+                continue;
+            }
+
+            unreadable.push(name.to_string());
+
+            let from_backup = backup
+                .as_ref()
+                .and_then(|backup| backup.get(name))
+                .filter(|value| readable(value));
+            if let Some(value) = from_backup {
+                warn!(
+                    "couldn't read `{}` from the save file, restoring it from the backup",
+                    name
+                );
+                upgraded.push(name, value.to_string());
+                continue;
+            }
+
+            warn!("couldn't read `{}` from the save file, resetting it", name);
+        }
+
+        match ron::to_string(&TypedReflectSerializer::new(default, registry)) {
+            Ok(default) => upgraded.push(name, default),
+            // bevy_simple_prefs will use the default anyway
+            Err(e) => warn!("couldn't write the default `{}`: {}", name, e),
+        }
+    }
+
+    Upgrade::Upgraded {
+        text: upgraded.to_ron(),
+        unreadable,
+    }
+}
+
+/// Upgrades the save before it's loaded.
+///
+/// The backup is only ever a save that was read without any problems, so a corrupt
+/// save can't cost the player their leaderboard identity. Saves with problems are
+/// copied aside before they're changed.
+fn prepare_save(registry: &TypeRegistry) {
+    let Some(text) = storage::read(SAVE_NAME) else {
+        return;
+    };
+
+    let backup_name = format!("{}.bak", SAVE_NAME);
+    let corrupt_name = format!("{}.corrupt", SAVE_NAME);
+    let backup = storage::read(&backup_name);
+
+    match upgrade(&text, backup.as_deref(), registry) {
+        Upgrade::Upgraded {
+            text: upgraded,
+            unreadable,
+        } => {
+            if unreadable.is_empty() {
+                storage::write(&backup_name, &text);
+            } else {
+                warn!("keeping the unreadable save file as {}", corrupt_name);
+                storage::write(&corrupt_name, &text);
+            }
+
+            if upgraded != text {
+                storage::write(SAVE_NAME, &upgraded);
+            }
+        }
+        Upgrade::Newer(version) => {
+            warn!(
+                "save file is from a newer version of the game ({} > {}), leaving it alone",
+                version, SAVE_VERSION
+            );
+        }
+        Upgrade::Corrupt => {
+            warn!("save file is corrupt, moving it to {}", corrupt_name);
+            storage::write(&corrupt_name, &text);
+
+            match backup.map(|backup| upgrade(&backup, None, registry)) {
+                Some(Upgrade::Upgraded { text, .. }) => {
+                    info!("restoring save file from {}", backup_name);
+                    storage::write(SAVE_NAME, &text);
+                }
+                _ => {
+                    warn!("no usable backup, starting with a new save file");
+                    storage::remove(SAVE_NAME);
+                }
+            }
+        }
+    }
+}
+
+/// Reads and writes the save where bevy_simple_prefs keeps it.
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use bevy::prelude::*;
+
+    pub fn read(name: &str) -> Option<String> {
+        // Not `read_to_string`, so that a file that isn't UTF-8 is treated as
+        // corrupt instead of missing.
+        let bytes = std::fs::read(name).ok()?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub fn write(name: &str, contents: &str) {
+        if let Err(e) = std::fs::write(name, contents) {
+            warn!("couldn't write {}: {}", name, e);
+        }
+    }
+
+    pub fn remove(name: &str) {
+        if let Err(e) = std::fs::remove_file(name) {
+            warn!("couldn't remove {}: {}", name, e);
+        }
+    }
+}
+
+/// Reads and writes the save where bevy_simple_prefs keeps it.
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use bevy::prelude::*;
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        local_storage()?.get_item(name).ok()?
+    }
+
+    pub fn write(name: &str, contents: &str) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if storage.set_item(name, contents).is_err() {
+            warn!("couldn't write {} to local storage", name);
+        }
+    }
+
+    pub fn remove(name: &str) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::*;
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        registry.register::<SaveFile>();
+        registry
+    }
+
+    /// Reads an upgraded save the way bevy_simple_prefs would.
+    fn load(text: &str, registry: &TypeRegistry) -> SaveFile {
+        let registration = registry.get(TypeId::of::<SaveFile>()).unwrap();
+        let value = ron::Options::default()
+            .from_str_seed(text, TypedReflectDeserializer::new(registration, registry))
+            .unwrap();
+        SaveFile::from_reflect(&*value).unwrap()
+    }
+
+    fn upgraded(upgrade: Upgrade) -> (String, Vec<String>) {
+        match upgrade {
+            Upgrade::Upgraded { text, unreadable } => (text, unreadable),
+            other => panic!("expected an upgraded save, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nested_values() {
+        let fields =
+            SaveFields::parse("(history: ({\"track\": [(time: 1.0, tricks: 2)]}), sfx: (20))")
+                .unwrap();
+
+        assert_eq!(
+            fields.get("history"),
+            Some("({\"track\": [(time: 1.0, tricks: 2)]})")
+        );
+        assert_eq!(fields.get("sfx"), Some("(20)"));
+    }
+
+    #[test]
+    fn strings_with_brackets_and_commas() {
+        let fields =
+            SaveFields::parse(r#"(player_name: (Some("a, (b] \" c")), sfx: (20))"#).unwrap();
+
+        assert_eq!(fields.get("player_name"), Some(r#"(Some("a, (b] \" c"))"#));
+        assert_eq!(fields.get("sfx"), Some("(20)"));
+    }
+
+    #[test]
+    fn comments() {
+        let text = "// a (comment, with brackets\n(\n    sfx: (20), /* music: (30), /* ) */ */\n    music: (40), // ) \n)";
+        let fields = SaveFields::parse(text).unwrap();
+
+        assert_eq!(fields.get("sfx"), Some("(20)"));
+        assert_eq!(fields.get("music"), Some("(40)"));
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert!(SaveFields::parse(r#"(player_name: (Some("abc)), sfx: (20))"#).is_none());
+        assert!(SaveFields::parse("(sfx: (20), music: (30)").is_none());
+        assert!(SaveFields::parse("(sfx: (20)) /* unclosed").is_none());
+        assert_eq!(
+            upgrade("(sfx: (20), music: [30)", None, &registry()),
+            Upgrade::Corrupt
+        );
+    }
+
+    #[test]
+    fn fills_in_missing_fields() {
+        let registry = registry();
+        let (text, unreadable) = upgraded(upgrade("(sfx: (20), shadow: High)", None, &registry));
+        assert!(unreadable.is_empty());
+
+        let save = load(&text, &registry);
+        assert_eq!(*save.sfx, 20);
+        assert_eq!(save.shadow, ShadowSetting::High);
+        assert_eq!(*save.music, *MusicSetting::default());
+        assert_eq!(save.version.0, SAVE_VERSION);
+    }
+
+    #[test]
+    fn drops_unknown_fields() {
+        let registry = registry();
+        let (text, unreadable) = upgraded(upgrade("(sfx: (20), retired: (1))", None, &registry));
+
+        assert!(unreadable.is_empty());
+        assert!(!text.contains("retired"));
+        assert_eq!(*load(&text, &registry).sfx, 20);
+    }
+
+    #[test]
+    fn unreadable_field() {
+        let registry = registry();
+        let (text, unreadable) =
+            upgraded(upgrade("(sfx: (\"loud\"), music: (20))", None, &registry));
+
+        assert_eq!(unreadable, vec!["sfx".to_string()]);
+        let save = load(&text, &registry);
+        assert_eq!(*save.sfx, *SfxSetting::default());
+        assert_eq!(*save.music, 20);
+    }
+
+    #[test]
+    fn unreadable_field_from_backup() {
+        let registry = registry();
+        let (text, unreadable) = upgraded(upgrade(
+            "(version: (1), sfx: (\"loud\"), music: (20))",
+            Some("(sfx: (70), music: (10))"),
+            &registry,
+        ));
+
+        assert_eq!(unreadable, vec!["sfx".to_string()]);
+        let save = load(&text, &registry);
+        assert_eq!(*save.sfx, 70);
+        assert_eq!(*save.music, 20);
+    }
+
+    #[test]
+    fn newer_version() {
+        assert_eq!(
+            upgrade(
+                &format!("(version: ({}), sfx: (20))", SAVE_VERSION + 1),
+                None,
+                &registry()
+            ),
+            Upgrade::Newer(SAVE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn current_save_is_unchanged() {
+        let registry = registry();
+        let (text, _) = upgraded(upgrade("(sfx: (20))", None, &registry));
+        let (again, unreadable) = upgraded(upgrade(&text, None, &registry));
+
+        assert!(unreadable.is_empty());
+        assert_eq!(again, text);
+    }
 }
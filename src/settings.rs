@@ -1,7 +1,27 @@
 use bevy::prelude::*;
-use std::fmt::Display;
+use std::{cmp::Ordering, collections::HashMap, fmt::Display};
 
-#[derive(Resource, Deref, DerefMut, Debug, Clone, Reflect)]
+use crate::mixer::AudioBus;
+
+/// Returns the value `offset` steps away from `current` in `values`, wrapping around
+/// at either end. A value that isn't in the list counts as the first one.
+pub fn cycle<T: PartialEq + Clone>(values: &[T], current: &T, offset: isize) -> T {
+    let i = values
+        .iter()
+        .position(|value| value == current)
+        .unwrap_or_default() as isize;
+    values[(i + offset).rem_euclid(values.len() as isize) as usize].clone()
+}
+
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct MasterVolumeSetting(u8);
+impl Default for MasterVolumeSetting {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
 pub struct MusicSetting(u8);
 impl Default for MusicSetting {
     fn default() -> Self {
@@ -9,7 +29,7 @@ impl Default for MusicSetting {
     }
 }
 
-#[derive(Resource, Deref, DerefMut, Debug, Clone, Reflect)]
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
 pub struct SfxSetting(u8);
 impl Default for SfxSetting {
     fn default() -> Self {
@@ -17,7 +37,43 @@ impl Default for SfxSetting {
     }
 }
 
-#[derive(Resource, Debug, Clone, Default, Reflect)]
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct UiVolumeSetting(u8);
+impl Default for UiVolumeSetting {
+    fn default() -> Self {
+        Self(50)
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Default, Reflect)]
+pub struct MutedBusesSetting {
+    pub master: bool,
+    pub music: bool,
+    pub sfx: bool,
+    pub ui: bool,
+}
+impl MutedBusesSetting {
+    pub fn is_muted(&self, bus: AudioBus) -> bool {
+        match bus {
+            AudioBus::Master => self.master,
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ui => self.ui,
+        }
+    }
+
+    pub fn toggle(&mut self, bus: AudioBus) {
+        let muted = match bus {
+            AudioBus::Master => &mut self.master,
+            AudioBus::Music => &mut self.music,
+            AudioBus::Sfx => &mut self.sfx,
+            AudioBus::Ui => &mut self.ui,
+        };
+        *muted = !*muted;
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
 pub enum ShadowSetting {
     None,
     Low,
@@ -26,29 +82,331 @@ pub enum ShadowSetting {
     High,
 }
 impl ShadowSetting {
-    pub fn next(&self) -> Self {
+    pub const ALL: &'static [Self] = &[Self::None, Self::Low, Self::Medium, Self::High];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+
+    /// The localization key of the setting's name.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::None => "shadow.none",
+            Self::Low => "shadow.low",
+            Self::Medium => "shadow.medium",
+            Self::High => "shadow.high",
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+impl WindowModeSetting {
+    pub const ALL: &'static [Self] = &[Self::Windowed, Self::Borderless, Self::Fullscreen];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+
+    /// The localization key of the setting's name.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Windowed => "window_mode.windowed",
+            Self::Borderless => "window_mode.borderless",
+            Self::Fullscreen => "window_mode.fullscreen",
+        }
+    }
+}
+
+/// The size of the window, when it's not fullscreen.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Reflect)]
+pub struct ResolutionSetting {
+    pub width: u32,
+    pub height: u32,
+}
+impl Default for ResolutionSetting {
+    fn default() -> Self {
+        Self::PRESETS[0].clone()
+    }
+}
+impl ResolutionSetting {
+    pub const PRESETS: &'static [Self] = &[
+        Self::new(1280, 720),
+        Self::new(1600, 900),
+        Self::new(1920, 1080),
+        Self::new(2560, 1440),
+    ];
+
+    const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::PRESETS, self, offset)
+    }
+}
+impl Display for ResolutionSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct VsyncSetting(pub bool);
+impl Default for VsyncSetting {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// The number of samples used for anti-aliasing.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum MsaaSetting {
+    Off,
+    Sample2,
+    #[default]
+    Sample4,
+    Sample8,
+}
+impl MsaaSetting {
+    pub const ALL: &'static [Self] = &[Self::Off, Self::Sample2, Self::Sample4, Self::Sample8];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+
+    pub fn samples(&self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::Sample2 => 2,
+            Self::Sample4 => 4,
+            Self::Sample8 => 8,
+        }
+    }
+}
+
+/// The most frames to draw each second. `None` leaves it up to vsync.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct FrameRateLimitSetting(pub Option<u16>);
+impl FrameRateLimitSetting {
+    pub const STEPS: &'static [Option<u16>] = &[None, Some(30), Some(60), Some(120), Some(144)];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        Self(cycle(Self::STEPS, &self.0, offset))
+    }
+}
+
+/// The resolution the game is drawn at, in percent of the window's. The UI is
+/// always drawn at full resolution.
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct RenderScaleSetting(u8);
+impl Default for RenderScaleSetting {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+impl RenderScaleSetting {
+    pub const STEPS: &'static [u8] = &[50, 75, 100];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        Self(cycle(Self::STEPS, &self.0, offset))
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.0 as f32 / 100.
+    }
+}
+
+/// How the camera follows the player.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum CameraSetting {
+    /// Looks ahead and pulls back with speed and altitude.
+    #[default]
+    Dynamic,
+    /// Stays centered on the player.
+    Classic,
+}
+impl CameraSetting {
+    pub const ALL: &'static [Self] = &[Self::Dynamic, Self::Classic];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+
+    /// The localization key of the setting's name.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Dynamic => "camera.dynamic",
+            Self::Classic => "camera.classic",
+        }
+    }
+}
+
+/// The colors of the UI.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum ThemeSetting {
+    #[default]
+    Default,
+    Colorblind,
+    HighContrast,
+}
+impl ThemeSetting {
+    pub const ALL: &'static [Self] = &[Self::Default, Self::Colorblind, Self::HighContrast];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+
+    /// The localization key of the setting's name.
+    pub fn key(&self) -> &'static str {
         match self {
-            Self::None => Self::Low,
-            Self::Low => Self::Medium,
-            Self::Medium => Self::High,
-            Self::High => Self::None,
+            Self::Default => "theme.default",
+            Self::Colorblind => "theme.colorblind",
+            Self::HighContrast => "theme.high_contrast",
         }
     }
 }
-impl Display for ShadowSetting {
+
+/// The size of the UI and its text, in percent.
+#[derive(Resource, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct TextScaleSetting(u8);
+impl Default for TextScaleSetting {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+impl TextScaleSetting {
+    pub const STEPS: &'static [u8] = &[75, 100, 125, 150];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        Self(cycle(Self::STEPS, &self.0, offset))
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.0 as f32 / 100.
+    }
+}
+
+/// The language of the UI text.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum LanguageSetting {
+    #[default]
+    English,
+    Spanish,
+    German,
+    French,
+}
+impl LanguageSetting {
+    pub const ALL: &'static [Self] = &[Self::English, Self::Spanish, Self::German, Self::French];
+
+    pub fn cycle(&self, offset: isize) -> Self {
+        cycle(Self::ALL, self, offset)
+    }
+}
+impl Display for LanguageSetting {
+    /// Each language is shown by its own name, so players can find theirs.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
             match self {
-                Self::None => "None",
-                Self::Low => "Low",
-                Self::Medium => "Medium",
-                Self::High => "High",
+                Self::English => "English",
+                Self::Spanish => "Español",
+                Self::German => "Deutsch",
+                Self::French => "Français",
             }
         )
     }
 }
 
+/// Turns off camera shake and the zoom animation.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, PartialEq, Reflect)]
+pub struct ReducedMotionSetting(pub bool);
+
 #[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
 pub struct LeaderboardSetting(pub Option<bevy_jornet::Player>);
+
+/// The player's display name. This is the name on the local leaderboard, and is
+/// kept in sync with the name of the online leaderboard player.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
+pub struct PlayerNameSetting(pub Option<String>);
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct LocalScore {
+    pub player: String,
+    pub score: f32,
+}
+
+/// The best times on this machine, keyed by track name.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
+pub struct LocalLeaderboardSetting(pub HashMap<String, Vec<LocalScore>>);
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct RunRecord {
+    pub time: f32,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub tricks: u32,
+    pub bonks: u32,
+    /// The race time at each checkpoint, in order
+    #[reflect(default)]
+    pub splits: Vec<f32>,
+}
+
+/// Finished runs, keyed by track name.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
+pub struct RunHistorySetting(pub HashMap<String, Vec<RunRecord>>);
+impl RunHistorySetting {
+    pub fn personal_best(&self, track: &str) -> Option<&RunRecord> {
+        self.get(track)?
+            .iter()
+            .min_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal))
+    }
+
+    /// The fastest run that went through all of the track's checkpoints.
+    pub fn best_with_splits(&self, track: &str, num_splits: usize) -> Option<&RunRecord> {
+        self.get(track)?
+            .iter()
+            .filter(|run| run.splits.len() == num_splits)
+            .min_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal))
+    }
+}
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct PendingScore {
+    pub score: f32,
+    /// An encoded `Replay` of the run, so the leaderboard can verify the score.
+    pub replay: String,
+}
+
+/// Scores that haven't made it to the leaderboard yet, oldest first.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
+pub struct PendingScoresSetting(pub Vec<PendingScore>);
+
+/// Counters that add up across every race the player has ever played.
+#[derive(Resource, Default, Debug, Clone, Reflect)]
+pub struct LifetimeStatsSetting {
+    pub races_finished: u32,
+    pub total_flips: u32,
+    pub mega_flips: u32,
+    pub bonks: u32,
+    pub lava_deaths: u32,
+}
+
+/// The version of the save file's layout. See `save::SAVE_VERSION`.
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct SaveVersionSetting(pub u32);
+impl Default for SaveVersionSetting {
+    fn default() -> Self {
+        Self(crate::save::SAVE_VERSION)
+    }
+}
+
+/// The ids of the achievements that have been unlocked.
+#[derive(Resource, Default, Deref, DerefMut, Debug, Clone, Reflect)]
+pub struct AchievementsSetting(pub Vec<String>);
@@ -0,0 +1,722 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_alt_ui_navigation_lite::{events::Direction, prelude::*};
+
+use crate::{
+    loading::{AudioAssets, GameAssets},
+    localization::{language_changed, Localization},
+    mixer::{AudioBus, Mixer, VolumeSettings},
+    settings::{
+        cycle, CameraSetting, FrameRateLimitSetting, LanguageSetting, MsaaSetting,
+        ReducedMotionSetting, RenderScaleSetting, ResolutionSetting, SfxSetting, ShadowSetting,
+        TextScaleSetting, ThemeSetting, VsyncSetting, WindowModeSetting,
+    },
+    ui::{buttons, theme_changed, Palette},
+    GameState,
+};
+
+const ARROW_SIZE: f32 = 40.;
+
+pub struct SettingsMenuPlugin;
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<SettingsMenu>()
+            .init_resource::<SettingsPage>()
+            .init_resource::<LastFocused>()
+            .add_systems(OnEnter(SettingsMenu::Open), spawn)
+            .add_systems(
+                Update,
+                (
+                    nav_actions,
+                    arrow_actions,
+                    update_values.after(nav_actions).after(arrow_actions),
+                    sfx_preview,
+                    buttons.after(NavRequestSystem),
+                )
+                    .run_if(in_state(SettingsMenu::Open)),
+            )
+            // Rebuild the screen in the new language or colors, or for another page
+            .add_systems(
+                Update,
+                (cleanup, spawn).chain().run_if(
+                    in_state(SettingsMenu::Open).and_then(
+                        language_changed
+                            .or_else(theme_changed)
+                            .or_else(page_changed),
+                    ),
+                ),
+            )
+            .add_systems(OnExit(SettingsMenu::Open), cleanup);
+    }
+}
+
+/// Whether the settings screen is showing. The main menu and the pause menu open it,
+/// and hide themselves until it closes.
+#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SettingsMenu {
+    #[default]
+    Closed,
+    Open,
+}
+
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+enum SettingsPage {
+    #[default]
+    General,
+    Audio,
+    Graphics,
+}
+impl SettingsPage {
+    const ALL: &'static [Self] = &[Self::General, Self::Audio, Self::Graphics];
+
+    /// The localization key of the page's name.
+    fn key(&self) -> &'static str {
+        match self {
+            Self::General => "settings.general",
+            Self::Audio => "settings.audio",
+            Self::Graphics => "settings.graphics",
+        }
+    }
+
+    fn options(&self) -> &'static [SettingsOption] {
+        match self {
+            Self::General => &[
+                SettingsOption::Camera,
+                SettingsOption::ReducedMotion,
+                SettingsOption::Language,
+                SettingsOption::Theme,
+                SettingsOption::TextScale,
+            ],
+            Self::Audio => &[
+                SettingsOption::Volume(AudioBus::Master),
+                SettingsOption::Volume(AudioBus::Music),
+                SettingsOption::Volume(AudioBus::Sfx),
+                SettingsOption::Volume(AudioBus::Ui),
+            ],
+            Self::Graphics => &[
+                SettingsOption::WindowMode,
+                SettingsOption::Resolution,
+                SettingsOption::Vsync,
+                SettingsOption::Msaa,
+                // Browsers already limit the frame rate, and we can't sleep on wasm.
+                #[cfg(not(target_arch = "wasm32"))]
+                SettingsOption::FrameRate,
+                SettingsOption::RenderScale,
+                SettingsOption::Shadows,
+            ],
+        }
+    }
+}
+
+/// A setting that can be changed with left and right.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum SettingsOption {
+    Page,
+    Camera,
+    ReducedMotion,
+    Language,
+    Theme,
+    TextScale,
+    Volume(AudioBus),
+    WindowMode,
+    Resolution,
+    Vsync,
+    Msaa,
+    FrameRate,
+    RenderScale,
+    Shadows,
+}
+impl SettingsOption {
+    /// The localization key of the option's label.
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Page => "settings.section",
+            Self::Camera => "settings.camera",
+            Self::ReducedMotion => "settings.reduced_motion",
+            Self::Language => "settings.language",
+            Self::Theme => "settings.theme",
+            Self::TextScale => "settings.text_size",
+            Self::Volume(bus) => bus.key(),
+            Self::WindowMode => "graphics.window_mode",
+            Self::Resolution => "graphics.resolution",
+            Self::Vsync => "graphics.vsync",
+            Self::Msaa => "graphics.msaa",
+            Self::FrameRate => "graphics.frame_rate",
+            Self::RenderScale => "graphics.render_scale",
+            Self::Shadows => "graphics.shadows",
+        }
+    }
+}
+
+#[derive(Component)]
+enum SettingsButton {
+    Reset,
+    Back,
+}
+
+/// A button for changing a setting with the mouse. These aren't focusable, so that
+/// left and right on a focused setting have nowhere to go but the setting itself.
+#[derive(Component)]
+struct SettingsArrow {
+    option: SettingsOption,
+    offset: isize,
+}
+
+#[derive(Component)]
+struct SettingsMarker;
+
+#[derive(Component)]
+struct OptionText(SettingsOption);
+
+#[derive(Component)]
+struct VolumeFill(AudioBus);
+
+/// The option to focus when the screen is rebuilt.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct LastFocused(Option<SettingsOption>);
+
+/// Every setting on the settings screen.
+#[derive(SystemParam)]
+struct Settings<'w> {
+    page: ResMut<'w, SettingsPage>,
+    volumes: VolumeSettings<'w>,
+    camera: ResMut<'w, CameraSetting>,
+    reduced_motion: ResMut<'w, ReducedMotionSetting>,
+    language: ResMut<'w, LanguageSetting>,
+    theme: ResMut<'w, ThemeSetting>,
+    text_scale: ResMut<'w, TextScaleSetting>,
+    window_mode: ResMut<'w, WindowModeSetting>,
+    resolution: ResMut<'w, ResolutionSetting>,
+    vsync: ResMut<'w, VsyncSetting>,
+    msaa: ResMut<'w, MsaaSetting>,
+    frame_rate: ResMut<'w, FrameRateLimitSetting>,
+    render_scale: ResMut<'w, RenderScaleSetting>,
+    shadows: ResMut<'w, ShadowSetting>,
+}
+impl Settings<'_> {
+    fn text(&self, option: SettingsOption, localization: &Localization) -> String {
+        match option {
+            SettingsOption::Page => localization.get(self.page.key()).to_string(),
+            SettingsOption::Camera => localization.get(self.camera.key()).to_string(),
+            SettingsOption::ReducedMotion => {
+                on_off_text(**self.reduced_motion, localization).to_string()
+            }
+            SettingsOption::Language => self.language.to_string(),
+            SettingsOption::Theme => localization.get(self.theme.key()).to_string(),
+            SettingsOption::TextScale => {
+                localization.format("settings.percent", &[&**self.text_scale])
+            }
+            SettingsOption::Volume(bus) => {
+                if self.volumes.muted.is_muted(bus) {
+                    localization.get("audio.muted").to_string()
+                } else {
+                    localization.format("settings.percent", &[&self.volumes.volume(bus)])
+                }
+            }
+            SettingsOption::WindowMode => localization.get(self.window_mode.key()).to_string(),
+            SettingsOption::Resolution => self.resolution.to_string(),
+            SettingsOption::Vsync => on_off_text(**self.vsync, localization).to_string(),
+            SettingsOption::Msaa => match *self.msaa {
+                MsaaSetting::Off => localization.get("common.off").to_string(),
+                _ => localization.format("graphics.samples", &[&self.msaa.samples()]),
+            },
+            SettingsOption::FrameRate => match **self.frame_rate {
+                Some(fps) => localization.format("graphics.fps", &[&fps]),
+                None => localization.get("graphics.unlimited").to_string(),
+            },
+            SettingsOption::RenderScale => {
+                localization.format("settings.percent", &[&**self.render_scale])
+            }
+            SettingsOption::Shadows => localization.get(self.shadows.key()).to_string(),
+        }
+    }
+
+    /// Moves `option` `offset` steps. Sliders stop at either end, and everything
+    /// else wraps around.
+    fn adjust(&mut self, option: SettingsOption, offset: isize) {
+        match option {
+            SettingsOption::Page => *self.page = cycle(SettingsPage::ALL, &self.page, offset),
+            SettingsOption::Camera => *self.camera = self.camera.cycle(offset),
+            SettingsOption::ReducedMotion => **self.reduced_motion = !**self.reduced_motion,
+            SettingsOption::Language => *self.language = self.language.cycle(offset),
+            SettingsOption::Theme => *self.theme = self.theme.cycle(offset),
+            SettingsOption::TextScale => *self.text_scale = self.text_scale.cycle(offset),
+            SettingsOption::Volume(bus) => self.volumes.adjust_volume(bus, offset),
+            SettingsOption::WindowMode => *self.window_mode = self.window_mode.cycle(offset),
+            SettingsOption::Resolution => *self.resolution = self.resolution.cycle(offset),
+            SettingsOption::Vsync => **self.vsync = !**self.vsync,
+            SettingsOption::Msaa => *self.msaa = self.msaa.cycle(offset),
+            SettingsOption::FrameRate => *self.frame_rate = self.frame_rate.cycle(offset),
+            SettingsOption::RenderScale => *self.render_scale = self.render_scale.cycle(offset),
+            SettingsOption::Shadows => *self.shadows = self.shadows.cycle(offset),
+        }
+    }
+
+    fn activate(&mut self, option: SettingsOption) {
+        match option {
+            SettingsOption::Volume(bus) => self.volumes.muted.toggle(bus),
+            _ => self.adjust(option, 1),
+        }
+    }
+
+    /// Puts the settings on the current page back to their defaults. The language is
+    /// left alone, so that nobody gets stuck in one they can't read.
+    fn reset(&mut self) {
+        info!("resetting {:?} settings", *self.page);
+
+        match *self.page {
+            SettingsPage::General => {
+                self.camera.set_if_neq(default());
+                self.reduced_motion.set_if_neq(default());
+                self.theme.set_if_neq(default());
+                self.text_scale.set_if_neq(default());
+            }
+            SettingsPage::Audio => self.volumes.reset(),
+            SettingsPage::Graphics => {
+                self.window_mode.set_if_neq(default());
+                self.resolution.set_if_neq(default());
+                self.vsync.set_if_neq(default());
+                self.msaa.set_if_neq(default());
+                self.frame_rate.set_if_neq(default());
+                self.render_scale.set_if_neq(default());
+                self.shadows.set_if_neq(default());
+            }
+        }
+    }
+
+    fn is_changed(&self) -> bool {
+        self.page.is_changed()
+            || self.volumes.is_changed()
+            || self.camera.is_changed()
+            || self.reduced_motion.is_changed()
+            || self.language.is_changed()
+            || self.theme.is_changed()
+            || self.text_scale.is_changed()
+            || self.window_mode.is_changed()
+            || self.resolution.is_changed()
+            || self.vsync.is_changed()
+            || self.msaa.is_changed()
+            || self.frame_rate.is_changed()
+            || self.render_scale.is_changed()
+            || self.shadows.is_changed()
+    }
+}
+
+fn on_off_text(on: bool, localization: &Localization) -> &str {
+    localization.get(if on { "common.on" } else { "common.off" })
+}
+
+fn volume_fill_color(muted: bool, palette: &Palette) -> Color {
+    if muted {
+        palette.locked_text
+    } else {
+        palette.boosted_text
+    }
+}
+
+fn spawn(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    settings: Settings,
+    last_focused: Res<LastFocused>,
+    mut requests: EventWriter<NavRequest>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+    game_state: Res<State<GameState>>,
+) {
+    let font = localization.font(&assets);
+
+    let title_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 60.0,
+        color: palette.title_text,
+    };
+    let label_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.title_text,
+    };
+    let hint_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 24.0,
+        color: palette.title_text,
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: palette.button_text,
+    };
+
+    let root = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            SettingsMarker,
+        ))
+        .id();
+
+    let container = commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            background_color: palette.container_background.into(),
+            ..default()
+        })
+        .id();
+
+    let title = commands
+        .spawn(
+            TextBundle::from_section(localization.get("settings.title"), title_text_style)
+                .with_style(Style {
+                    margin: UiRect {
+                        bottom: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+        )
+        .id();
+
+    let mut focus = None;
+
+    let list = commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: vec![GridTrack::auto(), GridTrack::auto()],
+                column_gap: Val::Px(20.),
+                align_items: AlignItems::Center,
+                margin: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            // The HUD is only localized when a race starts, so the language can't be
+            // changed from the pause menu.
+            let racing = *game_state.get() == GameState::Playing;
+            let options = std::iter::once(&SettingsOption::Page)
+                .chain(settings.page.options())
+                .filter(|option| !(racing && **option == SettingsOption::Language));
+
+            for option in options {
+                let entity = spawn_row(
+                    parent,
+                    localization.get(option.key()),
+                    &label_text_style,
+                    Some((*option, &button_text_style)),
+                    &palette,
+                    *option,
+                    |parent| {
+                        let text = settings.text(*option, &localization);
+                        let text_style = TextStyle {
+                            font: localization.font_for(&text, &assets),
+                            ..button_text_style.clone()
+                        };
+
+                        if let SettingsOption::Volume(bus) = option {
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        width: Val::Px(120.),
+                                        height: Val::Px(10.),
+                                        margin: UiRect::right(Val::Px(10.)),
+                                        ..default()
+                                    },
+                                    background_color: palette.container_background.into(),
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        NodeBundle {
+                                            style: Style {
+                                                width: Val::Percent(
+                                                    settings.volumes.volume(*bus) as f32
+                                                ),
+                                                height: Val::Percent(100.),
+                                                ..default()
+                                            },
+                                            background_color: volume_fill_color(
+                                                settings.volumes.muted.is_muted(*bus),
+                                                &palette,
+                                            )
+                                            .into(),
+                                            ..default()
+                                        },
+                                        VolumeFill(*bus),
+                                    ));
+                                });
+                        }
+
+                        parent.spawn((
+                            TextBundle::from_section(text, text_style),
+                            OptionText(*option),
+                        ));
+                    },
+                );
+
+                if **last_focused == Some(*option) {
+                    focus = Some(entity);
+                }
+            }
+
+            for (button, key) in [
+                (SettingsButton::Reset, "settings.reset"),
+                (SettingsButton::Back, "common.back"),
+            ] {
+                spawn_row(
+                    parent,
+                    "",
+                    &label_text_style,
+                    None,
+                    &palette,
+                    button,
+                    |parent| {
+                        parent.spawn(TextBundle::from_section(
+                            localization.get(key),
+                            button_text_style.clone(),
+                        ));
+                    },
+                );
+            }
+        })
+        .id();
+
+    let mut hint = localization.get("settings.hint").to_string();
+    if *settings.page == SettingsPage::Audio {
+        hint.push('\n');
+        hint.push_str(localization.get("settings.mute_hint"));
+    }
+    let hint = commands
+        .spawn(
+            TextBundle::from_section(hint, hint_text_style).with_text_justify(JustifyText::Center),
+        )
+        .id();
+
+    commands.entity(root).push_children(&[container]);
+
+    commands
+        .entity(container)
+        .push_children(&[title, list, hint]);
+
+    if let Some(entity) = focus {
+        requests.send(NavRequest::FocusOn(entity));
+    }
+}
+
+/// Spawns a label and a focusable control into the settings grid, and returns the
+/// control. With `arrows`, the control gets buttons for changing it with a mouse.
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    label: &str,
+    label_text_style: &TextStyle,
+    arrows: Option<(SettingsOption, &TextStyle)>,
+    palette: &Palette,
+    control: impl Bundle,
+    content: impl FnOnce(&mut ChildBuilder),
+) -> Entity {
+    parent.spawn(TextBundle::from_section(label, label_text_style.clone()));
+
+    let mut entity = Entity::PLACEHOLDER;
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            spawn_arrow(parent, arrows, -1, "<", palette);
+
+            entity = parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(250.0),
+                            height: Val::Px(40.0),
+                            margin: UiRect::all(Val::Px(3.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: palette.normal_button.into(),
+                        ..default()
+                    },
+                    Focusable::default(),
+                    control,
+                ))
+                .with_children(content)
+                .id();
+
+            spawn_arrow(parent, arrows, 1, ">", palette);
+        });
+
+    entity
+}
+
+fn spawn_arrow(
+    parent: &mut ChildBuilder,
+    arrows: Option<(SettingsOption, &TextStyle)>,
+    offset: isize,
+    text: &str,
+    palette: &Palette,
+) {
+    let style = Style {
+        width: Val::Px(ARROW_SIZE),
+        height: Val::Px(ARROW_SIZE),
+        margin: UiRect::all(Val::Px(3.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    let Some((option, text_style)) = arrows else {
+        // Keeps every control in the same column, so that left and right never move
+        // the focus.
+        parent.spawn(NodeBundle { style, ..default() });
+        return;
+    };
+
+    parent
+        .spawn((
+            ButtonBundle {
+                style,
+                background_color: palette.normal_button.into(),
+                ..default()
+            },
+            SettingsArrow { option, offset },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(text, text_style.clone()));
+        });
+}
+
+fn nav_actions(
+    mut events: EventReader<NavEvent>,
+    options: Query<&SettingsOption>,
+    buttons: Query<&SettingsButton>,
+    mut settings: Settings,
+    mut last_focused: ResMut<LastFocused>,
+    mut next_state: ResMut<NextState<SettingsMenu>>,
+) {
+    for event in events.read() {
+        match event {
+            NavEvent::FocusChanged { to, .. } => {
+                if let Ok(option) = options.get(*to.first()) {
+                    **last_focused = Some(*option);
+                }
+            }
+            NavEvent::NoChanges { from, request } => {
+                let entity = *from.first();
+
+                if let Ok(option) = options.get(entity) {
+                    match request {
+                        NavRequest::Action => settings.activate(*option),
+                        NavRequest::Move(Direction::East) => settings.adjust(*option, 1),
+                        NavRequest::Move(Direction::West) => settings.adjust(*option, -1),
+                        _ => {}
+                    }
+                }
+
+                match (request, buttons.get(entity)) {
+                    (NavRequest::Action, Ok(SettingsButton::Reset)) => settings.reset(),
+                    (NavRequest::Action, Ok(SettingsButton::Back)) | (NavRequest::Cancel, _) => {
+                        next_state.set(SettingsMenu::Closed);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn arrow_actions(
+    mut arrow_query: Query<
+        (&Interaction, &SettingsArrow, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut settings: Settings,
+    palette: Res<Palette>,
+) {
+    for (interaction, arrow, mut color) in &mut arrow_query {
+        *color = match interaction {
+            Interaction::Pressed => {
+                settings.adjust(arrow.option, arrow.offset);
+                palette.pressed_button
+            }
+            Interaction::Hovered => palette.hovered_button,
+            Interaction::None => palette.normal_button,
+        }
+        .into();
+    }
+}
+
+fn update_values(
+    settings: Settings,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+    mut text_query: Query<(&mut Text, &OptionText)>,
+    mut fill_query: Query<(&mut Style, &mut BackgroundColor, &VolumeFill)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (mut text, OptionText(option)) in &mut text_query {
+        text.sections[0].value = settings.text(*option, &localization);
+    }
+
+    for (mut style, mut color, VolumeFill(bus)) in &mut fill_query {
+        style.width = Val::Percent(settings.volumes.volume(*bus) as f32);
+        *color = volume_fill_color(settings.volumes.muted.is_muted(*bus), &palette).into();
+    }
+}
+
+fn sfx_preview(
+    mut commands: Commands,
+    sfx_setting: Res<SfxSetting>,
+    mixer: Res<Mixer>,
+    game_audio: Res<AudioAssets>,
+) {
+    // Do not run when SfxSetting is first added by SavePlugin
+    if !sfx_setting.is_changed() || sfx_setting.is_added() {
+        return;
+    }
+
+    mixer.play(&mut commands, game_audio.trick.clone(), AudioBus::Sfx);
+}
+
+fn cleanup(mut commands: Commands, query: Query<Entity, With<SettingsMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// A run condition that is true when the page changed since the last time it was
+/// checked.
+fn page_changed(page: Res<SettingsPage>, mut last: Local<Option<SettingsPage>>) -> bool {
+    let changed = last.is_some_and(|l| l != *page);
+    *last = Some(*page);
+    changed
+}
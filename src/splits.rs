@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::CollisionEvent;
+
+use crate::{
+    history::LastRun,
+    loading::{GameAssets, TRACK_NAME},
+    localization::Localization,
+    settings::{RunHistorySetting, RunRecord},
+    ui::{GameUiMarker, Palette},
+    AfterPhysics, GameState, Player, RaceTime, Wheel,
+};
+
+pub struct SplitsPlugin;
+impl Plugin for SplitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Splits>()
+            .init_resource::<SplitComparison>()
+            .add_systems(OnEnter(GameState::Playing), (start_splits, spawn).chain())
+            .add_systems(
+                PostUpdate,
+                checkpoint_collisions
+                    .run_if(in_state(GameState::Playing))
+                    .in_set(AfterPhysics),
+            )
+            .add_systems(Update, update_splits.run_if(in_state(GameState::Playing)))
+            // Keep the splits up to date when crossing the finish line
+            .add_systems(
+                Update,
+                update_splits.run_if(in_state(GameState::Leaderboard)),
+            );
+    }
+}
+
+/// A sensor on the track. Checkpoints must be crossed in order, and the race time at
+/// each one is a split.
+#[derive(Component)]
+pub struct Checkpoint(pub u32);
+
+#[derive(Resource, Default)]
+pub struct Splits {
+    /// The order of the track's checkpoints.
+    order: Vec<u32>,
+    /// The race time at each checkpoint we've crossed so far.
+    pub times: Vec<f32>,
+}
+
+/// The personal best that this run is up against.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct SplitComparison(Option<RunRecord>);
+
+#[derive(Component)]
+struct SplitText(usize);
+#[derive(Component)]
+struct PredictedText;
+
+fn start_splits(
+    checkpoints: Query<&Checkpoint>,
+    history: Res<RunHistorySetting>,
+    mut splits: ResMut<Splits>,
+    mut comparison: ResMut<SplitComparison>,
+) {
+    let mut order: Vec<u32> = checkpoints.iter().map(|checkpoint| checkpoint.0).collect();
+    order.sort();
+
+    comparison.0 = history.best_with_splits(TRACK_NAME, order.len()).cloned();
+
+    splits.order = order;
+    splits.times.clear();
+}
+
+fn checkpoint_collisions(
+    mut collision_events: EventReader<CollisionEvent>,
+    checkpoint_query: Query<&Checkpoint>,
+    player_query: Query<Entity, Or<(With<Player>, With<Wheel>)>>,
+    race_time: Res<RaceTime>,
+    mut splits: ResMut<Splits>,
+) {
+    for collision_event in collision_events.read() {
+        let CollisionEvent::Started(e1, e2, _) = collision_event else {
+            continue;
+        };
+
+        if player_query.iter_many([e1, e2]).count() == 0 {
+            continue;
+        }
+
+        let Some(checkpoint) = checkpoint_query.iter_many([e1, e2]).next() else {
+            continue;
+        };
+
+        // Several colliders cross each checkpoint, and backtracking shouldn't count.
+        if splits.order.get(splits.times.len()) != Some(&checkpoint.0) {
+            continue;
+        }
+
+        let time = race_time.elapsed_secs();
+        info!("split {}: {:.3}", splits.times.len() + 1, time);
+        splits.times.push(time);
+    }
+}
+
+fn spawn(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    splits: Res<Splits>,
+    palette: Res<Palette>,
+) {
+    // Only tracks with checkpoints have splits.
+    if splits.order.is_empty() {
+        return;
+    }
+
+    let split_text_style = TextStyle {
+        font: localization.font(&assets),
+        font_size: 30.0,
+        color: palette.title_text,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(5.),
+                    top: Val::Px(70.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
+                ..default()
+            },
+            GameUiMarker,
+        ))
+        .with_children(|parent| {
+            // One for each checkpoint and one for the finish line
+            for i in 0..=splits.order.len() {
+                parent.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new("", split_text_style.clone()),
+                        TextSection::new("", split_text_style.clone()),
+                        TextSection::new("", split_text_style.clone()),
+                    ]),
+                    SplitText(i),
+                ));
+            }
+
+            parent.spawn((
+                TextBundle::from_section("", split_text_style.clone()),
+                PredictedText,
+            ));
+        });
+}
+
+fn update_splits(
+    splits: Res<Splits>,
+    comparison: Res<SplitComparison>,
+    last_run: Res<LastRun>,
+    mut split_query: Query<(&mut Text, &SplitText), Without<PredictedText>>,
+    mut predicted_query: Query<&mut Text, With<PredictedText>>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    if !splits.is_changed() && !comparison.is_changed() && !last_run.is_changed() {
+        return;
+    }
+
+    let finish_time = last_run.0.as_ref().map(|(run, _)| run.time);
+
+    // The finish line is the last split.
+    let times: Vec<f32> = splits.times.iter().copied().chain(finish_time).collect();
+    let best_times: Option<Vec<f32>> = comparison
+        .0
+        .as_ref()
+        .map(|best| best.splits.iter().copied().chain([best.time]).collect());
+
+    for (mut text, SplitText(i)) in &mut split_query {
+        text.sections[0].value = if *i < splits.order.len() {
+            format!("{} ", localization.format("splits.split", &[&(i + 1)]))
+        } else {
+            format!("{} ", localization.get("splits.finish"))
+        };
+
+        let best = best_times.as_ref().and_then(|best| best.get(*i)).copied();
+
+        match (times.get(*i), best) {
+            (Some(time), Some(best)) => {
+                let delta = time - best;
+                text.sections[1].value = format!("{:.3}", time);
+                text.sections[1].style.color = palette.title_text;
+                text.sections[2].value = format!(" {:+.3}", delta);
+                text.sections[2].style.color = if delta < 0. {
+                    palette.faster_text
+                } else {
+                    palette.slower_text
+                };
+            }
+            (Some(time), None) => {
+                text.sections[1].value = format!("{:.3}", time);
+                text.sections[1].style.color = palette.title_text;
+                text.sections[2].value = String::new();
+            }
+            (None, Some(best)) => {
+                text.sections[1].value = format!("{:.3}", best);
+                text.sections[1].style.color = palette.locked_text;
+                text.sections[2].value = String::new();
+            }
+            (None, None) => {
+                text.sections[1].value = "-".to_string();
+                text.sections[1].style.color = palette.locked_text;
+                text.sections[2].value = String::new();
+            }
+        }
+    }
+
+    let predicted = match (finish_time, comparison.0.as_ref()) {
+        (Some(time), _) => Some(time),
+        (None, Some(best)) => {
+            // Assume we'll keep whatever lead or deficit we had at the last split.
+            let delta = splits
+                .times
+                .len()
+                .checked_sub(1)
+                .and_then(|i| Some(splits.times[i] - best.splits.get(i)?))
+                .unwrap_or(0.);
+            Some(best.time + delta)
+        }
+        (None, None) => None,
+    };
+
+    for mut text in &mut predicted_query {
+        text.sections[0].value = match predicted {
+            Some(predicted) => {
+                localization.format("splits.predicted", &[&format!("{:.3}", predicted)])
+            }
+            None => String::new(),
+        };
+    }
+}
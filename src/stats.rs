@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    localization::Localization,
+    ui::{kph, Palette},
+    AfterPhysics, Boost, GameSet, GameState, Player, RaceTime, Trick, WheelsOnGround,
+};
+
+pub struct StatsPlugin;
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>().add_systems(
+            PostUpdate,
+            track_stats
+                .after(GameSet::Movement)
+                .run_if(in_state(GameState::Playing))
+                .in_set(AfterPhysics),
+        );
+    }
+}
+
+/// Things that happened during the current run, for the records and the
+/// post-race statistics.
+#[derive(Resource, Default)]
+pub struct RunStats {
+    /// The number of tricks landed
+    pub tricks: u32,
+    pub bonks: u32,
+    pub front_flips: u32,
+    pub back_flips: u32,
+    pub unique_tricks: HashSet<Trick>,
+    /// Seconds spent with no wheels on the ground
+    pub airtime: f32,
+    pub longest_hang_time: f32,
+    pub boosted_time: f32,
+    pub top_speed: f32,
+    pub distance: f32,
+    /// Seconds spent racing, so that we can work out the average speed
+    pub elapsed: f32,
+}
+impl RunStats {
+    pub fn average_speed(&self) -> f32 {
+        if self.elapsed > 0. {
+            self.distance / self.elapsed
+        } else {
+            0.
+        }
+    }
+}
+
+fn track_stats(
+    time: Res<Time>,
+    race_time: Res<RaceTime>,
+    query: Query<(&Velocity, &WheelsOnGround, &Boost), With<Player>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    // Don't count the countdown, or the moments after crossing the finish line.
+    if race_time.paused() {
+        return;
+    }
+
+    let elapsed = time.delta_seconds();
+
+    for (velocity, wheels, boost) in &query {
+        let speed = velocity.linvel.length();
+
+        run_stats.elapsed += elapsed;
+        run_stats.distance += speed * elapsed;
+        run_stats.top_speed = run_stats.top_speed.max(speed);
+
+        if **wheels == 0 {
+            run_stats.airtime += elapsed;
+        }
+        if boost.remaining > 0. {
+            run_stats.boosted_time += elapsed;
+        }
+    }
+}
+
+/// Spawns a panel with the statistics for the current run.
+pub fn spawn_run_stats(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    localization: &Localization,
+    stats: &RunStats,
+    palette: &Palette,
+) -> Entity {
+    let seconds = |secs: String| localization.format("stats.seconds", &[&secs]);
+    let speed = |speed: f32| localization.format("stats.kph", &[&format!("{:.0}", kph(speed))]);
+
+    let title_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 40.0,
+        color: palette.title_text,
+    };
+    let stat_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 24.0,
+        color: palette.title_text,
+    };
+
+    let rows = [
+        ("stats.airtime", seconds(format!("{:.1}", stats.airtime))),
+        (
+            "stats.longest_hang_time",
+            seconds(format!("{:.2}", stats.longest_hang_time)),
+        ),
+        ("stats.front_flips", stats.front_flips.to_string()),
+        ("stats.back_flips", stats.back_flips.to_string()),
+        ("stats.unique_tricks", stats.unique_tricks.len().to_string()),
+        ("stats.bonks", stats.bonks.to_string()),
+        (
+            "stats.time_boosted",
+            seconds(format!("{:.1}", stats.boosted_time)),
+        ),
+        ("stats.top_speed", speed(stats.top_speed)),
+        ("stats.average_speed", speed(stats.average_speed())),
+    ];
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            background_color: palette.container_background.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(localization.get("stats.title"), title_text_style)
+                    .with_style(Style {
+                        margin: UiRect {
+                            bottom: Val::Px(10.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+            );
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: vec![GridTrack::auto(), GridTrack::auto()],
+                        column_gap: Val::Px(20.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (label, value) in rows {
+                        parent.spawn(TextBundle::from_section(
+                            localization.get(label),
+                            stat_text_style.clone(),
+                        ));
+                        parent.spawn(
+                            TextBundle::from_section(value, stat_text_style.clone()).with_style(
+                                Style {
+                                    justify_self: JustifySelf::End,
+                                    ..default()
+                                },
+                            ),
+                        );
+                    }
+                });
+        })
+        .id()
+}
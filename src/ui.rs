@@ -3,14 +3,26 @@ use bevy_alt_ui_navigation_lite::prelude::*;
 use bevy_rapier3d::prelude::Velocity;
 use interpolation::Ease;
 
-use crate::{AfterPhysics, Boost, GameAssets, GameSet, GameState, Player, RaceTime, Trick};
+use crate::{
+    localization::Localization,
+    settings::{TextScaleSetting, ThemeSetting},
+    AfterPhysics, Boost, GameAssets, GameSet, GameState, Player, RaceTime, Trick,
+};
 
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<TrickText>()
             .init_resource::<TrickTextTimer>()
-            .add_systems(OnExit(GameState::MainMenu), setup)
+            .init_resource::<Palette>()
+            .add_systems(
+                PreUpdate,
+                (
+                    apply_theme.run_if(resource_exists::<ThemeSetting>),
+                    apply_text_scale.run_if(resource_exists::<TextScaleSetting>),
+                ),
+            )
+            .add_systems(OnEnter(GameState::Playing), setup)
             .add_systems(
                 Update,
                 (fade_trick_text, race_time, trick_text, boost_gauge)
@@ -34,16 +46,138 @@ impl Plugin for UiPlugin {
     }
 }
 
-pub const FOCUSED_BUTTON: Color = Color::srgb(0.25, 0.0, 0.25);
-pub const FOCUSED_HOVERED_BUTTON: Color = Color::srgb(0.35, 0.0, 0.35);
-pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
-pub const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
-pub const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
-pub const BUTTON_TEXT: Color = Color::srgb(0.9, 0.9, 0.9);
-pub const TITLE_TEXT: Color = Color::srgb(0.9, 0.9, 0.9);
-pub const BOOSTED_TEXT: Color = Color::srgb(0.55, 0.0, 0.55);
-pub const OUR_SCORE_TEXT: Color = Color::srgb(0.55, 0.0, 0.55);
-pub const CONTAINER_BACKGROUND: Color = Color::srgb(0.1, 0.1, 0.1);
+/// The colors used by the UI, which change with the `ThemeSetting`.
+#[derive(Resource, Clone, Debug)]
+pub struct Palette {
+    pub theme: ThemeSetting,
+    pub focused_button: Color,
+    pub focused_hovered_button: Color,
+    pub normal_button: Color,
+    pub hovered_button: Color,
+    pub pressed_button: Color,
+    pub button_text: Color,
+    pub title_text: Color,
+    pub boosted_text: Color,
+    pub our_score_text: Color,
+    pub faster_text: Color,
+    pub slower_text: Color,
+    pub locked_text: Color,
+    pub container_background: Color,
+    /// The trick and countdown text
+    pub trick_text: Color,
+    /// Whether text fades out gradually, rather than staying solid until it disappears.
+    pub fade_text: bool,
+}
+impl Palette {
+    pub const DEFAULT: Self = Self {
+        theme: ThemeSetting::Default,
+        focused_button: Color::srgb(0.25, 0.0, 0.25),
+        focused_hovered_button: Color::srgb(0.35, 0.0, 0.35),
+        normal_button: Color::srgb(0.15, 0.15, 0.15),
+        hovered_button: Color::srgb(0.25, 0.25, 0.25),
+        pressed_button: Color::srgb(0.35, 0.75, 0.35),
+        button_text: Color::srgb(0.9, 0.9, 0.9),
+        title_text: Color::srgb(0.9, 0.9, 0.9),
+        boosted_text: Color::srgb(0.55, 0.0, 0.55),
+        our_score_text: Color::srgb(0.55, 0.0, 0.55),
+        faster_text: Color::srgb(0.35, 0.75, 0.35),
+        slower_text: Color::srgb(0.85, 0.25, 0.25),
+        locked_text: Color::srgb(0.45, 0.45, 0.45),
+        container_background: Color::srgb(0.1, 0.1, 0.1),
+        trick_text: Color::srgb(1.0, 0.0, 0.0),
+        fade_text: true,
+    };
+
+    /// Avoids telling things apart by red and green alone. Based on the Okabe-Ito
+    /// palette.
+    pub const COLORBLIND: Self = Self {
+        theme: ThemeSetting::Colorblind,
+        focused_button: Color::srgb(0.0, 0.3, 0.5),
+        focused_hovered_button: Color::srgb(0.0, 0.4, 0.65),
+        pressed_button: Color::srgb(0.35, 0.7, 0.9),
+        boosted_text: Color::srgb(0.8, 0.6, 0.7),
+        our_score_text: Color::srgb(0.95, 0.9, 0.25),
+        faster_text: Color::srgb(0.35, 0.7, 0.9),
+        slower_text: Color::srgb(0.9, 0.6, 0.0),
+        trick_text: Color::srgb(0.95, 0.9, 0.25),
+        ..Self::DEFAULT
+    };
+
+    /// Pure black and white, with bright accents and no fading text.
+    pub const HIGH_CONTRAST: Self = Self {
+        theme: ThemeSetting::HighContrast,
+        focused_button: Color::srgb(0.0, 0.2, 0.9),
+        focused_hovered_button: Color::srgb(0.2, 0.4, 1.0),
+        normal_button: Color::BLACK,
+        hovered_button: Color::srgb(0.3, 0.3, 0.3),
+        pressed_button: Color::srgb(0.0, 0.6, 0.0),
+        button_text: Color::WHITE,
+        title_text: Color::WHITE,
+        boosted_text: Color::srgb(1.0, 0.3, 1.0),
+        our_score_text: Color::srgb(1.0, 1.0, 0.0),
+        faster_text: Color::srgb(0.3, 1.0, 0.3),
+        slower_text: Color::srgb(1.0, 0.35, 0.35),
+        locked_text: Color::srgb(0.7, 0.7, 0.7),
+        container_background: Color::BLACK,
+        trick_text: Color::srgb(1.0, 1.0, 0.0),
+        fade_text: false,
+    };
+
+    pub fn for_theme(theme: &ThemeSetting) -> Self {
+        match theme {
+            ThemeSetting::Default => Self::DEFAULT,
+            ThemeSetting::Colorblind => Self::COLORBLIND,
+            ThemeSetting::HighContrast => Self::HIGH_CONTRAST,
+        }
+    }
+
+    /// The colors that play a part in the UI, with the ones the HUD uses first. Some
+    /// palettes use one color for several parts, and the HUD's should win.
+    fn colors(&self) -> [Color; 14] {
+        [
+            self.title_text,
+            self.boosted_text,
+            self.trick_text,
+            self.faster_text,
+            self.slower_text,
+            self.locked_text,
+            self.container_background,
+            self.button_text,
+            self.our_score_text,
+            self.normal_button,
+            self.hovered_button,
+            self.focused_button,
+            self.focused_hovered_button,
+            self.pressed_button,
+        ]
+    }
+
+    /// The color in this palette that plays the part `color` plays in `old`, keeping
+    /// its alpha. Colors that aren't from `old` are left alone.
+    fn translate(&self, old: &Palette, color: Color) -> Color {
+        old.colors()
+            .iter()
+            .position(|c| c.with_alpha(1.) == color.with_alpha(1.))
+            .map(|i| self.colors()[i].with_alpha(color.alpha()))
+            .unwrap_or(color)
+    }
+
+    /// `color` at `alpha`, or at full strength until it disappears if fading is off.
+    pub fn fade(&self, color: Color, alpha: f32) -> Color {
+        if self.fade_text {
+            color.with_alpha(alpha)
+        } else if alpha > 0. {
+            color
+        } else {
+            Color::NONE
+        }
+    }
+}
+impl Default for Palette {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
 
 pub const BOOST_PX_PER_SECOND: f32 = 30.;
 pub const BOOST_NOTCH_PX: f32 = 2.;
@@ -70,7 +204,14 @@ pub struct BoostRightNode;
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct TrickText(String);
 
-fn setup(mut commands: Commands, assets: Res<GameAssets>) {
+fn setup(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    localization: Res<Localization>,
+    palette: Res<Palette>,
+) {
+    let font = localization.font(&assets);
+
     commands
         .spawn((
             NodeBundle {
@@ -94,9 +235,9 @@ fn setup(mut commands: Commands, assets: Res<GameAssets>) {
                     text: Text::from_section(
                         "0.000",
                         TextStyle {
-                            font: assets.font.clone(),
+                            font: font.clone(),
                             font_size: 60.0,
-                            color: Color::WHITE,
+                            color: palette.title_text,
                         },
                     ),
                     ..Default::default()
@@ -120,7 +261,7 @@ fn setup(mut commands: Commands, assets: Res<GameAssets>) {
             text: Text::from_section(
                 "",
                 TextStyle {
-                    font: assets.font.clone(),
+                    font: font.clone(),
                     font_size: 60.0,
                     color: Color::NONE,
                 },
@@ -158,11 +299,11 @@ fn setup(mut commands: Commands, assets: Res<GameAssets>) {
             parent.spawn((
                 TextBundle {
                     text: Text::from_section(
-                        "0 kph",
+                        localization.format("stats.kph", &[&0]),
                         TextStyle {
-                            font: assets.font.clone(),
+                            font: font.clone(),
                             font_size: 60.0,
-                            color: TITLE_TEXT,
+                            color: palette.title_text,
                         },
                     ),
                     ..Default::default()
@@ -217,7 +358,7 @@ fn setup(mut commands: Commands, assets: Res<GameAssets>) {
                                 height: Val::Percent(100.),
                                 ..default()
                             },
-                            background_color: BOOSTED_TEXT.into(),
+                            background_color: palette.boosted_text.into(),
                             ..default()
                         });
                     }
@@ -245,7 +386,7 @@ fn setup(mut commands: Commands, assets: Res<GameAssets>) {
                                 height: Val::Percent(100.),
                                 ..default()
                             },
-                            background_color: BOOSTED_TEXT.into(),
+                            background_color: palette.boosted_text.into(),
                             ..default()
                         });
                     }
@@ -257,62 +398,58 @@ fn fade_trick_text(
     time: Res<Time>,
     mut timer: ResMut<TrickTextTimer>,
     mut query: Query<&mut Text, With<TrickTextMarker>>,
+    palette: Res<Palette>,
 ) {
     timer.tick(time.delta());
     if !timer.finished() {
         for mut text in query.iter_mut() {
-            text.sections[0].style.color =
-                Color::srgba(1., 0., 0., Ease::cubic_out(timer.fraction_remaining()))
+            text.sections[0].style.color = palette.fade(
+                palette.trick_text,
+                Ease::cubic_out(timer.fraction_remaining()),
+            )
         }
     } else if timer.just_finished() {
         for mut text in query.iter_mut() {
-            text.sections[0].style.color = Color::srgba(1., 0., 0., 0.)
+            text.sections[0].style.color = Color::NONE
         }
     }
 }
 
-pub fn get_trick_text(trick: &Trick) -> String {
+pub fn get_trick_text(trick: &Trick, localization: &Localization) -> String {
     fn num_text(num: u32) -> Option<&'static str> {
         match num {
             0 | 1 => None,
-            2 => Some("Double"),
-            3 => Some("Triple"),
-            4 => Some("Quad"),
-            _ => Some("Mega"),
+            2 => Some("trick.double"),
+            3 => Some("trick.triple"),
+            4 => Some("trick.quad"),
+            _ => Some("trick.mega"),
         }
     }
 
-    let mut lines = vec![];
-
-    if trick.front_flips > 0 {
-        let mut parts = vec![];
-
-        if trick.fakie {
-            parts.push("Fakie");
-        }
+    // The word order depends on the language, so each flip has its own template,
+    // and any unused parts are squeezed out afterwards.
+    let line = |key: &str, num: u32| {
+        let fakie = if trick.fakie {
+            localization.get("trick.fakie")
+        } else {
+            ""
+        };
+        let num = num_text(num).map(|key| localization.get(key)).unwrap_or("");
 
-        if let Some(num) = num_text(trick.front_flips) {
-            parts.push(num);
-        }
+        localization
+            .format(key, &[&fakie, &num])
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
 
-        parts.push("Front Flip!");
+    let mut lines = vec![];
 
-        lines.push(parts.join(" "));
+    if trick.front_flips > 0 {
+        lines.push(line("trick.front_flip", trick.front_flips));
     }
     if trick.back_flips > 0 {
-        let mut parts = vec![];
-
-        if trick.fakie {
-            parts.push("Fakie");
-        }
-
-        if let Some(num) = num_text(trick.back_flips) {
-            parts.push(num);
-        }
-
-        parts.push("Back Flip!");
-
-        lines.push(parts.join(" "));
+        lines.push(line("trick.back_flip", trick.back_flips));
     }
 
     lines.join("\n")
@@ -332,6 +469,7 @@ fn trick_text(
     mut timer: ResMut<TrickTextTimer>,
     mut text_node: Query<&mut Text, With<TrickTextMarker>>,
     text: Res<TrickText>,
+    palette: Res<Palette>,
 ) {
     if !text.is_changed() {
         return;
@@ -339,7 +477,7 @@ fn trick_text(
 
     for mut node in text_node.iter_mut() {
         node.sections[0].value.clone_from(&**text);
-        node.sections[0].style.color = Color::srgba(1., 0., 0., 1.)
+        node.sections[0].style.color = palette.trick_text
     }
 
     timer.reset();
@@ -348,19 +486,29 @@ fn trick_text(
 fn speedometer_text(
     query: Query<(&Velocity, &Boost), With<Player>>,
     mut text_query: Query<&mut Text, With<SpeedometerText>>,
+    palette: Res<Palette>,
+    localization: Res<Localization>,
 ) {
     for (velocity, boost) in query.iter() {
         for mut text in text_query.iter_mut() {
-            text.sections[0].value = format!("{:.0} kph", (velocity.linvel.length() * 3.5).round());
+            text.sections[0].value = localization.format(
+                "stats.kph",
+                &[&format!("{:.0}", kph(velocity.linvel.length()).round())],
+            );
             if boost.remaining > 0.0 {
-                text.sections[0].style.color = BOOSTED_TEXT
+                text.sections[0].style.color = palette.boosted_text
             } else {
-                text.sections[0].style.color = TITLE_TEXT
+                text.sections[0].style.color = palette.title_text
             }
         }
     }
 }
 
+/// Converts a speed in world units per second into the kph shown to the player.
+pub fn kph(speed: f32) -> f32 {
+    speed * 3.5
+}
+
 fn boost_gauge(
     query: Query<&Boost, Changed<Boost>>,
     mut left_query: Query<&mut Style, (With<BoostLeftNode>, Without<BoostRightNode>)>,
@@ -382,24 +530,25 @@ pub fn buttons(
         (&Interaction, &Focusable, &mut BackgroundColor),
         (Or<(Changed<Interaction>, Changed<Focusable>)>, With<Button>),
     >,
+    palette: Res<Palette>,
 ) {
     for (interaction, focusable, mut color) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
-                *color = PRESSED_BUTTON.into();
+                *color = palette.pressed_button.into();
             }
             Interaction::Hovered => {
                 if matches!(focusable.state(), FocusState::Focused) {
-                    *color = FOCUSED_HOVERED_BUTTON.into()
+                    *color = palette.focused_hovered_button.into()
                 } else {
-                    *color = HOVERED_BUTTON.into();
+                    *color = palette.hovered_button.into();
                 };
             }
             Interaction::None => {
                 if matches!(focusable.state(), FocusState::Focused) {
-                    *color = FOCUSED_BUTTON.into()
+                    *color = palette.focused_button.into()
                 } else {
-                    *color = NORMAL_BUTTON.into();
+                    *color = palette.normal_button.into();
                 };
             }
         }
@@ -411,3 +560,69 @@ fn cleanup(mut commands: Commands, query: Query<Entity, With<GameUiMarker>>) {
         commands.entity(entity).despawn_recursive();
     }
 }
+
+fn apply_theme(
+    theme: Res<ThemeSetting>,
+    mut palette: ResMut<Palette>,
+    mut text_query: Query<&mut Text>,
+    mut background_query: Query<&mut BackgroundColor>,
+) {
+    if !theme.is_changed() || palette.theme == *theme {
+        return;
+    }
+
+    info!("switching to the {:?} theme", *theme);
+
+    let new_palette = Palette::for_theme(&theme);
+
+    // Menus are rebuilt when the theme changes, but the HUD is only spawned when a race
+    // starts, so recolor everything that's already on screen.
+    for mut text in &mut text_query {
+        for section in &mut text.sections {
+            section.style.color = new_palette.translate(&palette, section.style.color);
+        }
+    }
+    for mut background in &mut background_query {
+        background.0 = new_palette.translate(&palette, background.0);
+    }
+
+    *palette = new_palette;
+}
+
+fn apply_text_scale(text_scale: Res<TextScaleSetting>, mut ui_scale: ResMut<UiScale>) {
+    if !text_scale.is_changed() {
+        return;
+    }
+
+    ui_scale.0 = text_scale.scale();
+}
+
+/// A run condition that is true when the theme changed since the last time it was
+/// checked. The first check only remembers the current theme.
+pub fn theme_changed(palette: Res<Palette>, mut last: Local<Option<ThemeSetting>>) -> bool {
+    let changed = last.as_ref().is_some_and(|theme| *theme != palette.theme);
+    *last = Some(palette.theme.clone());
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_palette_colors() {
+        let (old, new) = (Palette::DEFAULT, Palette::HIGH_CONTRAST);
+
+        assert_eq!(new.translate(&old, old.title_text), new.title_text);
+        assert_eq!(
+            new.translate(&old, old.container_background.with_alpha(0.6)),
+            new.container_background.with_alpha(0.6)
+        );
+        // The default palette shares this color with `our_score_text`, which the HUD
+        // doesn't use.
+        assert_eq!(new.translate(&old, old.boosted_text), new.boosted_text);
+
+        let other = Color::srgb(0.1, 0.2, 0.3);
+        assert_eq!(new.translate(&old, other), other);
+    }
+}